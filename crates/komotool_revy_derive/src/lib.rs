@@ -0,0 +1,452 @@
+//! Derive macros for `komotool_revy`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{Data, DeriveInput, parse_macro_input, spanned::Spanned as _};
+
+// ---
+
+/// Derives `komotool_revy::RerunLog`, see its documentation for the supported field attributes.
+///
+/// The type must also reflect `RerunLog` (i.e. `#[reflect(RerunLog)]`) so that the sync can find
+/// its logger, unless it is marked with `#[rerun(manual)]`, in which case it must be registered
+/// using `register_rerun_log` instead.
+#[proc_macro_derive(RerunLog, attributes(rerun))]
+pub fn derive_rerun_log(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    rerun_log(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// How a field gets logged, as specified by its `#[rerun(...)]` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldKind {
+    Scalar,
+    Points3D,
+    Arrow,
+    Text,
+
+    /// No attribute at all: logged as text, using its `Debug` implementation.
+    Debug,
+
+    Skip,
+}
+
+fn field_kind(field: &syn::Field) -> syn::Result<FieldKind> {
+    let mut kind = None;
+
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("rerun"))
+    {
+        attr.parse_nested_meta(|meta| {
+            let new_kind = if meta.path.is_ident("scalar") {
+                FieldKind::Scalar
+            } else if meta.path.is_ident("points3d") {
+                FieldKind::Points3D
+            } else if meta.path.is_ident("arrow") {
+                FieldKind::Arrow
+            } else if meta.path.is_ident("text") {
+                FieldKind::Text
+            } else if meta.path.is_ident("skip") {
+                FieldKind::Skip
+            } else {
+                return Err(
+                    meta.error("expected one of `scalar`, `points3d`, `arrow`, `text` or `skip`")
+                );
+            };
+
+            if kind.replace(new_kind).is_some() {
+                return Err(meta.error("a field can only be logged in one way"));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(kind.unwrap_or(FieldKind::Debug))
+}
+
+/// Whether the type is marked with `#[rerun(manual)]`, see [`derive_rerun_log`].
+fn is_manual(input: &DeriveInput) -> syn::Result<bool> {
+    let mut manual = false;
+
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("rerun"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("manual") {
+                manual = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `manual`"))
+            }
+        })?;
+    }
+
+    Ok(manual)
+}
+
+/// Whether the type has `#[reflect(..., RerunLog, ...)]`.
+fn reflects_rerun_log(input: &DeriveInput) -> syn::Result<bool> {
+    let mut reflected = false;
+
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("reflect"))
+    {
+        let metas = attr.parse_args_with(
+            syn::punctuated::Punctuated::<syn::Meta, syn::Token![,]>::parse_terminated,
+        )?;
+        reflected |= metas.iter().any(|meta| {
+            meta.path()
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == "RerunLog")
+        });
+    }
+
+    Ok(reflected)
+}
+
+/// A field to log: its name, how to access it, and how to log it.
+struct LoggedField {
+    name: String,
+    member: TokenStream2,
+    kind: FieldKind,
+}
+
+fn rerun_log(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.span(),
+            "`RerunLog` can only be derived for structs",
+        ));
+    };
+
+    // Without it, nothing would ever get logged, and nothing would tell why.
+    if !is_manual(input)? && !reflects_rerun_log(input)? {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "`RerunLog` must be reflected for the sync to find it: add `#[reflect(RerunLog)]` \
+             and register the type with `App::register_type`, or add `#[rerun(manual)]` and \
+             register it with `register_rerun_log`",
+        ));
+    }
+
+    let mut fields = Vec::new();
+    for (index, field) in data.fields.iter().enumerate() {
+        let (name, member) = if let Some(ident) = &field.ident {
+            (ident.to_string(), quote!(#ident))
+        } else {
+            let index = syn::Index::from(index);
+            (index.index.to_string(), quote!(#index))
+        };
+
+        fields.push(LoggedField {
+            name,
+            member,
+            kind: field_kind(field)?,
+        });
+    }
+
+    let revy = quote!(::komotool_revy);
+    let rerun = quote!(#revy::external::rerun);
+
+    let of_kind = |kinds: &[FieldKind]| {
+        let fields = fields
+            .iter()
+            .filter(|field| kinds.contains(&field.kind))
+            .collect::<Vec<_>>();
+        let names = fields.iter().map(|field| &field.name).collect::<Vec<_>>();
+        let members = fields.iter().map(|field| &field.member).collect::<Vec<_>>();
+        (fields.is_empty(), names, members)
+    };
+
+    // Fields of the same kind are logged together as a single multi-instance archetype, so they
+    // don't overwrite each other.
+
+    let scalars = match of_kind(&[FieldKind::Scalar]) {
+        (true, _, _) => None,
+        (false, names, members) => Some(quote! {
+            batches.extend(#rerun::AsComponents::as_serialized_batches(
+                &#rerun::Scalars::new([#(self.#members as f64),*]),
+            ));
+            batches.extend(#rerun::AsComponents::as_serialized_batches(
+                &#rerun::SeriesLines::new().with_names([#(#names),*]),
+            ));
+        }),
+    };
+
+    let points = match of_kind(&[FieldKind::Points3D]) {
+        (true, _, _) => None,
+        (false, names, members) => Some(quote! {
+            batches.extend(#rerun::AsComponents::as_serialized_batches(
+                &#rerun::Points3D::new([
+                    #(<[f32; 3]>::from(::std::clone::Clone::clone(&self.#members))),*
+                ])
+                .with_labels([#(#names),*]),
+            ));
+        }),
+    };
+
+    let arrows = match of_kind(&[FieldKind::Arrow]) {
+        (true, _, _) => None,
+        (false, names, members) => Some(quote! {
+            batches.extend(#rerun::AsComponents::as_serialized_batches(
+                &#rerun::Arrows3D::from_vectors([
+                    #(<[f32; 3]>::from(::std::clone::Clone::clone(&self.#members))),*
+                ])
+                .with_labels([#(#names),*]),
+            ));
+        }),
+    };
+
+    let text = match of_kind(&[FieldKind::Text, FieldKind::Debug]) {
+        (true, _, _) => None,
+        (false, _, members) => {
+            let format = fields
+                .iter()
+                .filter_map(|field| match field.kind {
+                    FieldKind::Text => Some(format!("{}: {{}}", field.name)),
+                    FieldKind::Debug => Some(format!("{}: {{:?}}", field.name)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            Some(quote! {
+                batches.extend(#rerun::AsComponents::as_serialized_batches(
+                    &#rerun::TextDocument::new(::std::format!(#format, #(&self.#members),*)),
+                ));
+            })
+        }
+    };
+
+    let ident = &input.ident;
+    let suffix = to_snake_case(&ident.to_string());
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #revy::RerunLog for #ident #ty_generics #where_clause {
+            fn rerun_log(&self, _ctx: &#revy::RerunLogContext<'_>) -> #revy::RerunLogBuilder {
+                #[allow(unused_mut)]
+                let mut batches = ::std::vec::Vec::new();
+
+                #scalars
+                #points
+                #arrows
+                #text
+
+                #revy::RerunLogBuilder::new().with_batches_at(#suffix, batches)
+            }
+        }
+    })
+}
+
+/// `PlayerStats` -> `player_stats`, `HTTPServer` -> `http_server`.
+fn to_snake_case(name: &str) -> String {
+    let chars = name.chars().collect::<Vec<_>>();
+
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (index, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && index > 0 {
+            // A new word starts either after a lowercase letter or a digit (`PlayerStats`), or at
+            // the last uppercase letter of an acronym (`HTTPServer`).
+            let prev = chars[index - 1];
+            let next = chars.get(index + 1);
+            if !prev.is_uppercase() || next.is_some_and(|next| next.is_lowercase()) {
+                snake.push('_');
+            }
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(input: TokenStream2) -> syn::Result<String> {
+        rerun_log(&syn::parse2(input).unwrap()).map(|expanded| expanded.to_string())
+    }
+
+    fn assert_expands_to(input: TokenStream2, expected: &[TokenStream2]) {
+        let expanded = expand(input).unwrap();
+        for expected in expected {
+            let expected = expected.to_string();
+            assert!(
+                expanded.contains(&expected),
+                "`{expected}` not found in `{expanded}`"
+            );
+        }
+    }
+
+    fn assert_error(input: TokenStream2, message: &str) {
+        let err = expand(input).unwrap_err().to_string();
+        assert!(err.contains(message), "`{message}` not found in `{err}`");
+    }
+
+    #[test]
+    fn scalar() {
+        assert_expands_to(
+            quote! {
+                #[reflect(Component, RerunLog)]
+                struct Stats {
+                    #[rerun(scalar)]
+                    health: f32,
+                    #[rerun(scalar)]
+                    mana: u32,
+                }
+            },
+            &[
+                quote!(Scalars::new([self.health as f64, self.mana as f64])),
+                quote!(SeriesLines::new().with_names(["health", "mana"])),
+                quote!(with_batches_at("stats", batches)),
+            ],
+        );
+    }
+
+    #[test]
+    fn points3d() {
+        assert_expands_to(
+            quote! {
+                #[reflect(RerunLog)]
+                struct Targets(#[rerun(points3d)] Vec3, #[rerun(points3d)] Vec3);
+            },
+            &[
+                quote!(Points3D::new([
+                    <[f32; 3]>::from(::std::clone::Clone::clone(&self.0)),
+                    <[f32; 3]>::from(::std::clone::Clone::clone(&self.1))
+                ])),
+                quote!(with_labels(["0", "1"])),
+            ],
+        );
+    }
+
+    #[test]
+    fn arrow() {
+        assert_expands_to(
+            quote! {
+                #[reflect(RerunLog)]
+                struct Motion {
+                    #[rerun(arrow)]
+                    velocity: Vec3,
+                }
+            },
+            &[
+                quote!(Arrows3D::from_vectors([<[f32; 3]>::from(
+                    ::std::clone::Clone::clone(&self.velocity)
+                )])),
+                quote!(with_labels(["velocity"])),
+            ],
+        );
+    }
+
+    #[test]
+    fn text_and_debug() {
+        assert_expands_to(
+            quote! {
+                #[reflect(RerunLog)]
+                struct Player {
+                    #[rerun(text)]
+                    name: String,
+                    state: PlayerState,
+                }
+            },
+            &[quote!(TextDocument::new(::std::format!(
+                "name: {}\nstate: {:?}",
+                &self.name,
+                &self.state
+            )))],
+        );
+    }
+
+    #[test]
+    fn skip() {
+        let expanded = expand(quote! {
+            #[reflect(RerunLog)]
+            struct Player {
+                #[rerun(scalar)]
+                health: f32,
+                #[rerun(skip)]
+                controller: ControllerHandle,
+            }
+        })
+        .unwrap();
+        assert!(!expanded.contains("controller"));
+        assert!(!expanded.contains("TextDocument"));
+    }
+
+    #[test]
+    fn manual() {
+        assert_expands_to(
+            quote! {
+                #[rerun(manual)]
+                struct Health(#[rerun(scalar)] f32);
+            },
+            &[quote!(impl ::komotool_revy::RerunLog for Health)],
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_error(
+            quote! {
+                struct Health(#[rerun(scalar)] f32);
+            },
+            "`RerunLog` must be reflected",
+        );
+        assert_error(
+            quote! {
+                #[reflect(Component)]
+                struct Health(#[rerun(scalar)] f32);
+            },
+            "`RerunLog` must be reflected",
+        );
+        assert_error(
+            quote! {
+                #[reflect(RerunLog)]
+                enum Health {}
+            },
+            "only be derived for structs",
+        );
+        assert_error(
+            quote! {
+                #[reflect(RerunLog)]
+                struct Health(#[rerun(plot)] f32);
+            },
+            "expected one of",
+        );
+        assert_error(
+            quote! {
+                #[reflect(RerunLog)]
+                struct Health(#[rerun(scalar, text)] f32);
+            },
+            "only be logged in one way",
+        );
+        assert_error(
+            quote! {
+                #[rerun(automatic)]
+                struct Health(#[rerun(scalar)] f32);
+            },
+            "expected `manual`",
+        );
+    }
+
+    #[test]
+    fn snake_case() {
+        assert_eq!(to_snake_case("Health"), "health");
+        assert_eq!(to_snake_case("PlayerStats"), "player_stats");
+        assert_eq!(to_snake_case("HTTPServer"), "http_server");
+        assert_eq!(to_snake_case("PlayerHTTP"), "player_http");
+        assert_eq!(to_snake_case("Vec3Stats"), "vec3_stats");
+    }
+}
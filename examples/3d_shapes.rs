@@ -49,7 +49,7 @@ fn main() {
             let rec = komotool_revy::RecordingStreamBuilder::new("3d_shapes")
                 .spawn()
                 .unwrap();
-            komotool_revy::RerunPlugin { rec }
+            komotool_revy::RerunPlugin::new(rec)
         })
         // ===============================================================================
         .add_systems(Startup, setup)
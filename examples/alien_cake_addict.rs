@@ -47,7 +47,7 @@ fn main() {
             let rec = komotool_revy::RecordingStreamBuilder::new("alien_cake_addict")
                 .spawn()
                 .unwrap();
-            komotool_revy::RerunPlugin { rec }
+            komotool_revy::RerunPlugin::new(rec)
         })
         // ===============================================================================
         .init_resource::<Game>()
@@ -77,7 +77,7 @@ fn main() {
             let rec = komotool_revy::RecordingStreamBuilder::new("breakout")
                 .spawn()
                 .unwrap();
-            komotool_revy::RerunPlugin { rec }
+            komotool_revy::RerunPlugin::new(rec)
         })
         // ===============================================================================
         .insert_resource(Score(0))
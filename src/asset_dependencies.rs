@@ -0,0 +1,142 @@
+use std::{any::TypeId, sync::Arc};
+
+use bevy::{
+    asset::UntypedAssetId,
+    ecs::event::EventCursor,
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+};
+
+// ---
+
+/// Declares that the output of a [`crate::RerunLogger`] depends on the contents of some assets of
+/// type `A`, as opposed to just the value of the component itself.
+///
+/// Whenever one of the assets referenced by an entity is added, modified or removed, the logger
+/// will run again for that entity (and that entity only).
+///
+/// See [`crate::RerunLogger::with_asset_dependency`].
+#[derive(Clone)]
+pub struct RerunAssetDependency {
+    asset_type_id: TypeId,
+
+    new_events_reader: fn() -> Box<dyn AssetEventsReader>,
+
+    /// Returns all the assets referenced by a given entity.
+    referenced_assets: Arc<dyn Fn(EntityRef<'_>, &mut Vec<UntypedAssetId>) + Send + Sync>,
+}
+
+impl std::fmt::Debug for RerunAssetDependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RerunAssetDependency")
+            .field("asset_type_id", &self.asset_type_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RerunAssetDependency {
+    /// `referenced_assets` returns the IDs of all the assets of type `A` that a given entity
+    /// depends on, e.g. the ID of its [`MeshMaterial3d`].
+    pub fn new<A, I>(referenced_assets: impl Fn(EntityRef<'_>) -> I + Send + Sync + 'static) -> Self
+    where
+        A: Asset,
+        I: IntoIterator<Item = AssetId<A>>,
+    {
+        Self {
+            asset_type_id: TypeId::of::<A>(),
+            new_events_reader: || Box::new(AssetEventsCursor::<A>::default()),
+            referenced_assets: Arc::new(move |entity: EntityRef<'_>, asset_ids: &mut Vec<_>| {
+                asset_ids.extend(referenced_assets(entity).into_iter().map(AssetId::untyped));
+            }),
+        }
+    }
+
+    /// Does `entity` reference any of the `modified_assets`?
+    pub(crate) fn is_affected(
+        &self,
+        entity: EntityRef<'_>,
+        modified_assets: &ModifiedAssets,
+    ) -> bool {
+        let Some(modified_assets) = modified_assets.get(&self.asset_type_id) else {
+            return false;
+        };
+
+        let mut asset_ids = Vec::new();
+        (self.referenced_assets)(entity, &mut asset_ids);
+
+        asset_ids
+            .iter()
+            .any(|asset_id| modified_assets.contains(asset_id))
+    }
+}
+
+// ---
+
+/// The IDs of all assets that changed since the last sync, grouped by asset type.
+pub(crate) type ModifiedAssets = HashMap<TypeId, HashSet<UntypedAssetId>>;
+
+/// Keeps track of which [`AssetEvent`]s have already been read, for every asset type that at
+/// least one logger depends on.
+#[derive(Default)]
+pub(crate) struct AssetEventsReaders(HashMap<TypeId, Box<dyn AssetEventsReader>>);
+
+impl AssetEventsReaders {
+    /// Reads all the asset events that were sent since the last call, for all the asset types
+    /// that `dependencies` depend on.
+    pub fn read_modified_assets<'a>(
+        &mut self,
+        world: &World,
+        dependencies: impl IntoIterator<Item = &'a RerunAssetDependency>,
+    ) -> ModifiedAssets {
+        for dependency in dependencies {
+            self.0
+                .entry(dependency.asset_type_id)
+                .or_insert_with(dependency.new_events_reader);
+        }
+
+        let mut modified_assets = ModifiedAssets::default();
+        for (asset_type_id, reader) in self.0.iter_mut() {
+            let mut asset_ids = HashSet::default();
+            reader.read_modified(world, &mut asset_ids);
+            if !asset_ids.is_empty() {
+                modified_assets.insert(*asset_type_id, asset_ids);
+            }
+        }
+
+        modified_assets
+    }
+}
+
+/// Type-erased [`EventCursor`] for the [`AssetEvent`]s of a given asset type.
+trait AssetEventsReader: Send + Sync {
+    fn read_modified(&mut self, world: &World, asset_ids: &mut HashSet<UntypedAssetId>);
+}
+
+struct AssetEventsCursor<A: Asset>(EventCursor<AssetEvent<A>>);
+
+impl<A: Asset> Default for AssetEventsCursor<A> {
+    fn default() -> Self {
+        Self(EventCursor::default())
+    }
+}
+
+impl<A: Asset> AssetEventsReader for AssetEventsCursor<A> {
+    fn read_modified(&mut self, world: &World, asset_ids: &mut HashSet<UntypedAssetId>) {
+        let Some(events) = world.get_resource::<Events<AssetEvent<A>>>() else {
+            return;
+        };
+
+        for event in self.0.read(events) {
+            match *event {
+                AssetEvent::Added { id }
+                | AssetEvent::Modified { id }
+                | AssetEvent::Removed { id }
+                | AssetEvent::LoadedWithDependencies { id } => {
+                    asset_ids.insert(id.untyped());
+                }
+
+                AssetEvent::Unused { .. } => {}
+            }
+        }
+    }
+}
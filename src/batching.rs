@@ -0,0 +1,280 @@
+use std::sync::Arc;
+
+use bevy::{platform::collections::HashMap, prelude::*};
+use rerun::external::{
+    arrow::{
+        array::{Array as _, ArrayRef, ListArray},
+        buffer::{NullBuffer, OffsetBuffer},
+        compute::concat,
+        datatypes::Field,
+        error::ArrowError,
+    },
+    re_log::ResultExt as _,
+};
+
+use crate::budget::CaptureTime;
+
+// ---
+
+/// How the sync sends its data to Rerun.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RerunSyncBatching {
+    /// Every log is sent on its own, using [`rerun::RecordingStream::log`], and left for the
+    /// batcher of the [`rerun::RecordingStream`] to merge into chunks, across frames.
+    #[default]
+    Rows,
+
+    /// Logs are accumulated over that many snapshots (i.e. frames, unless several
+    /// [`crate::RerunSamplePoint`]s are in use), then sent as columns grouped by component
+    /// descriptor, one [`rerun::RecordingStream::send_columns`] call per entity path.
+    ///
+    /// This cuts down on per-frame overhead when lots of entities change every frame. Every call
+    /// produces its own chunks though, so make sure to accumulate enough snapshots.
+    Columns { snapshots: u32 },
+}
+
+// ---
+
+/// Where the logs end up, on the background logging worker.
+///
+/// Whatever is still accumulated gets sent when the sink is dropped.
+pub(crate) struct LogSink {
+    rec: rerun::RecordingStream,
+
+    batching: RerunSyncBatching,
+
+    /// The columns accumulated so far, see [`RerunSyncBatching::Columns`].
+    ///
+    /// Rows that don't share the same timelines cannot share the same columns, hence the sample
+    /// timeline being part of the key.
+    columns: HashMap<(rerun::EntityPath, Option<&'static str>), PendingColumns>,
+
+    /// How many snapshots went by since the columns were last sent.
+    snapshots: u32,
+}
+
+impl Drop for LogSink {
+    fn drop(&mut self) {
+        self.send_columns();
+    }
+}
+
+impl LogSink {
+    pub fn new(rec: rerun::RecordingStream) -> Self {
+        Self {
+            rec,
+            batching: RerunSyncBatching::default(),
+            columns: Default::default(),
+            snapshots: 0,
+        }
+    }
+
+    pub fn set_batching(&mut self, batching: RerunSyncBatching) {
+        if batching == RerunSyncBatching::Rows {
+            self.send_columns();
+        }
+
+        self.batching = batching;
+    }
+
+    /// Logs `batches` at `entity_path`, as of `time`.
+    pub fn log(
+        &mut self,
+        time: CaptureTime,
+        entity_path: rerun::EntityPath,
+        batches: Vec<rerun::SerializedComponentBatch>,
+    ) {
+        match self.batching {
+            RerunSyncBatching::Rows => {
+                time.set(&self.rec);
+                self.rec.log(entity_path, &batches).ok_or_log_error();
+                time.unset_sample(&self.rec);
+            }
+
+            RerunSyncBatching::Columns { .. } => {
+                let timeline = time.sample.map(|sample| sample.timeline);
+                self.columns
+                    .entry((entity_path, timeline))
+                    .or_default()
+                    .push(time, batches);
+            }
+        }
+    }
+
+    /// Sends the accumulated columns if enough snapshots went by.
+    pub fn end_snapshot(&mut self) {
+        let RerunSyncBatching::Columns { snapshots } = self.batching else {
+            return;
+        };
+
+        self.snapshots += 1;
+        if self.snapshots >= snapshots {
+            self.send_columns();
+        }
+    }
+
+    fn send_columns(&mut self) {
+        let _trace = info_span!("send_columns").entered();
+
+        self.snapshots = 0;
+
+        for ((entity_path, timeline), columns) in self.columns.drain() {
+            columns.send(&self.rec, entity_path, timeline);
+        }
+    }
+}
+
+/// All the rows accumulated for a given entity path.
+#[derive(Default)]
+struct PendingColumns {
+    times: Vec<CaptureTime>,
+
+    /// The data of every row, grouped by component descriptor.
+    components: HashMap<rerun::ComponentDescriptor, Vec<(usize, ArrayRef)>>,
+}
+
+impl PendingColumns {
+    fn push(&mut self, time: CaptureTime, batches: Vec<rerun::SerializedComponentBatch>) {
+        let row = self.times.len();
+        self.times.push(time);
+
+        for batch in batches {
+            self.components
+                .entry(batch.descriptor)
+                .or_default()
+                .push((row, batch.array));
+        }
+    }
+
+    fn send(
+        self,
+        rec: &rerun::RecordingStream,
+        entity_path: rerun::EntityPath,
+        timeline: Option<&'static str>,
+    ) {
+        let Self { times, components } = self;
+        let num_rows = times.len();
+
+        let mut indexes = vec![rerun::TimeColumn::new_duration_secs(
+            "sim_time",
+            times.iter().map(|time| time.sim_time),
+        )];
+        if let Some(timeline) = timeline {
+            let samples = times.iter().filter_map(|time| time.sample);
+            indexes.push(rerun::TimeColumn::new_sequence(
+                "sim_sample",
+                samples.clone().map(|sample| sample.global_index),
+            ));
+            indexes.push(rerun::TimeColumn::new_sequence(
+                timeline,
+                samples.map(|sample| sample.index),
+            ));
+        }
+
+        let columns = components
+            .into_iter()
+            .filter_map(|(descriptor, rows)| {
+                let list_array = to_list_array(num_rows, &rows)
+                    .map_err(|err| format!("couldn't batch {descriptor} at {entity_path}: {err}"))
+                    .ok_or_log_error()?;
+                Some(rerun::SerializedComponentColumn {
+                    list_array,
+                    descriptor,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        rec.send_columns(entity_path, indexes, columns)
+            .ok_or_log_error();
+    }
+}
+
+/// Packs sparse `rows` into a single list array of `num_rows` entries.
+///
+/// Rows with no data are null, which Rerun treats as "nothing was logged", as opposed to empty
+/// lists which actually clear the data.
+///
+/// Rows with several arrays only keep the last one, just like [`rerun::RecordingStream::log`]
+/// does with batches that share the same descriptor.
+fn to_list_array(num_rows: usize, rows: &[(usize, ArrayRef)]) -> Result<ListArray, ArrowError> {
+    let mut arrays = vec![None; num_rows];
+    for (row, array) in rows {
+        arrays[*row] = Some(&**array);
+    }
+
+    let values = concat(&arrays.iter().flatten().copied().collect::<Vec<_>>())?;
+    let offsets = OffsetBuffer::from_lengths(
+        arrays
+            .iter()
+            .map(|array| array.map_or(0, |array| array.len())),
+    );
+    let nulls = NullBuffer::from(arrays.iter().map(Option::is_some).collect::<Vec<_>>());
+    let field = Arc::new(Field::new_list_field(values.data_type().clone(), true));
+
+    ListArray::try_new(field, offsets, values, Some(nulls))
+}
+
+#[cfg(test)]
+mod tests {
+    use rerun::external::arrow::array::{Array as _, Int32Array};
+
+    use super::*;
+
+    fn ints(values: &[i32]) -> ArrayRef {
+        Arc::new(Int32Array::from(values.to_vec()))
+    }
+
+    fn row(list_array: &ListArray, index: usize) -> Option<Vec<i32>> {
+        list_array.is_valid(index).then(|| {
+            let values = list_array.value(index);
+            let values = values.as_any().downcast_ref::<Int32Array>().unwrap();
+            values.values().to_vec()
+        })
+    }
+
+    #[test]
+    fn sparse_rows_are_null() {
+        let list_array = to_list_array(5, &[(1, ints(&[1, 2])), (3, ints(&[3]))]).unwrap();
+
+        assert_eq!(list_array.len(), 5);
+        assert_eq!(row(&list_array, 0), None);
+        assert_eq!(row(&list_array, 1), Some(vec![1, 2]));
+        assert_eq!(row(&list_array, 2), None);
+        assert_eq!(row(&list_array, 3), Some(vec![3]));
+        assert_eq!(row(&list_array, 4), None);
+    }
+
+    #[test]
+    fn empty_rows_are_empty_lists() {
+        // Empty batches clear the data, they must not be mistaken for missing rows.
+        let list_array = to_list_array(3, &[(0, ints(&[])), (2, ints(&[4]))]).unwrap();
+
+        assert_eq!(row(&list_array, 0), Some(vec![]));
+        assert_eq!(row(&list_array, 1), None);
+        assert_eq!(row(&list_array, 2), Some(vec![4]));
+    }
+
+    #[test]
+    fn dense_rows() {
+        let list_array = to_list_array(2, &[(0, ints(&[1])), (1, ints(&[2, 3]))]).unwrap();
+
+        assert_eq!(list_array.null_count(), 0);
+        assert_eq!(row(&list_array, 0), Some(vec![1]));
+        assert_eq!(row(&list_array, 1), Some(vec![2, 3]));
+    }
+
+    #[test]
+    fn duplicate_rows_keep_the_last_array() {
+        // E.g. two components converting to the same archetype, at the same path.
+        let list_array = to_list_array(
+            3,
+            &[(0, ints(&[1, 2])), (0, ints(&[3])), (2, ints(&[4, 5]))],
+        )
+        .unwrap();
+
+        assert_eq!(list_array.values().len(), 3);
+        assert_eq!(row(&list_array, 0), Some(vec![3]));
+        assert_eq!(row(&list_array, 1), None);
+        assert_eq!(row(&list_array, 2), Some(vec![4, 5]));
+    }
+}
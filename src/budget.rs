@@ -0,0 +1,157 @@
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+
+use crate::worker::{PendingLog, QueuedBatch};
+
+// ---
+
+/// Limits how many of the changes the sync captures every frame, i.e. how much time it spends
+/// hashing components and running their loggers.
+///
+/// Whatever doesn't fit in the budget is carried over to the next frames, in round-robin order
+/// across entities. Carried over changes are still logged as of the `sim_time` at which they were
+/// detected, unless their entity changed again in the meantime.
+///
+/// The budget is meant to absorb spikes (level loads, mass spawns, etc): if more changes than the
+/// budget allows for keep coming in, the backlog will keep on growing.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub enum RerunSyncBudget {
+    /// Capture everything right away.
+    #[default]
+    Unlimited,
+
+    /// Stop capturing once that much time was spent capturing during the current frame.
+    Time(Duration),
+
+    /// Stop capturing once that many bytes worth of component data were captured during the
+    /// current frame.
+    ///
+    /// This doesn't account for the components logged by the generic reflection-based logger,
+    /// which only get serialized by the background logging worker.
+    Bytes(u64),
+}
+
+/// Keeps track of how much of the [`RerunSyncBudget`] was spent during the current frame.
+pub(crate) struct CaptureBudget {
+    budget: RerunSyncBudget,
+    start: Instant,
+    bytes: u64,
+}
+
+impl CaptureBudget {
+    pub fn new(budget: RerunSyncBudget) -> Self {
+        Self {
+            budget,
+            start: Instant::now(),
+            bytes: 0,
+        }
+    }
+
+    /// Accounts for `batches` being captured.
+    pub fn spend<'a>(&mut self, batches: impl IntoIterator<Item = &'a QueuedBatch>) {
+        self.bytes += batches.into_iter().map(QueuedBatch::num_bytes).sum::<u64>();
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        match self.budget {
+            RerunSyncBudget::Unlimited => false,
+            RerunSyncBudget::Time(duration) => self.start.elapsed() >= duration,
+            RerunSyncBudget::Bytes(max_bytes) => self.bytes >= max_bytes,
+        }
+    }
+}
+
+// ---
+
+/// The point in time at which some data was captured.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct CaptureTime {
+    pub sim_time: f64,
+
+    /// The [`bevy::diagnostic::FrameCount`], which isn't logged as a timeline (yet).
+    pub frame: u32,
+
+    /// Only set when more than one [`crate::RerunSamplePoint`] is in use.
+    pub sample: Option<SampleTime>,
+}
+
+/// Which snapshot some data was captured in, see [`crate::RerunSamplePoint`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SampleTime {
+    /// The name of the sample point, which is also the name of its timeline.
+    pub timeline: &'static str,
+
+    /// How many snapshots were taken at this sample point before this one.
+    pub index: i64,
+
+    /// How many snapshots were taken at any sample point before this one.
+    pub global_index: i64,
+}
+
+impl CaptureTime {
+    /// Makes this the time at which everything gets logged from now on, on the current thread.
+    pub fn set(&self, rec: &rerun::RecordingStream) {
+        rec.set_duration_secs("sim_time", self.sim_time);
+
+        if let Some(sample) = self.sample {
+            rec.set_time_sequence("sim_sample", sample.global_index);
+            rec.set_time_sequence(sample.timeline, sample.index);
+        }
+    }
+
+    /// Undoes [`Self::set`] for the sample timelines, so they don't leak into unrelated logs.
+    pub fn unset_sample(&self, rec: &rerun::RecordingStream) {
+        if let Some(sample) = self.sample {
+            rec.disable_timeline("sim_sample");
+            rec.disable_timeline(sample.timeline);
+        }
+    }
+}
+
+/// Everything the sync wants to log during the current frame.
+#[derive(Default)]
+pub(crate) struct LogQueue {
+    /// The time at which the data being pushed right now was captured.
+    capture_time: CaptureTime,
+
+    /// The pending logs, in the order they were pushed in.
+    pending: Vec<PendingLog>,
+}
+
+impl LogQueue {
+    pub fn set_capture_time(&mut self, time: CaptureTime) {
+        self.capture_time = time;
+    }
+
+    #[inline]
+    pub fn capture_time(&self) -> CaptureTime {
+        self.capture_time
+    }
+
+    /// Runs `f`, pretending that whatever it pushes was captured at `time`.
+    pub fn with_capture_time<R>(&mut self, time: CaptureTime, f: impl FnOnce(&mut Self) -> R) -> R {
+        let capture_time = std::mem::replace(&mut self.capture_time, time);
+        let res = f(self);
+        self.capture_time = capture_time;
+        res
+    }
+
+    /// Queues `batches` to be logged at `entity_path`.
+    pub fn push(&mut self, entity_path: rerun::EntityPath, batches: Vec<QueuedBatch>) {
+        if batches.is_empty() {
+            return;
+        }
+
+        self.pending.push(PendingLog {
+            time: self.capture_time,
+            entity_path,
+            batches,
+        });
+    }
+
+    /// Takes all the pending logs.
+    pub fn flush(&mut self) -> Vec<PendingLog> {
+        std::mem::take(&mut self.pending)
+    }
+}
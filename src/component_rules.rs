@@ -0,0 +1,223 @@
+use bevy::{ecs::component::ComponentId, platform::collections::HashSet, prelude::*};
+use serde::Deserialize;
+
+use crate::{
+    RerunComponentLoggers, RerunTypedComponentLoggers, rerun_logger::get_registered_logger,
+};
+
+// ---
+
+/// Decides whether the components whose fully-qualified type path matches `pattern` get synced.
+///
+/// Patterns are globs where `*` matches any sequence of characters (including `::`), e.g.
+/// `bevy_render::*`, `bevy_pbr::*::*Cascade*` or `my_game::ai::*`.
+/// A pattern without any `*` must match the type path exactly.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum RerunComponentRule {
+    Allow(String),
+    Deny(String),
+}
+
+impl RerunComponentRule {
+    #[inline]
+    pub fn allow(pattern: impl Into<String>) -> Self {
+        Self::Allow(pattern.into())
+    }
+
+    #[inline]
+    pub fn deny(pattern: impl Into<String>) -> Self {
+        Self::Deny(pattern.into())
+    }
+
+    #[inline]
+    pub fn pattern(&self) -> &str {
+        match self {
+            Self::Allow(pattern) | Self::Deny(pattern) => pattern,
+        }
+    }
+
+    pub fn matches(&self, component_name: &str) -> bool {
+        glob_matches(self.pattern(), component_name)
+    }
+}
+
+/// Ordered allow/deny rules for components, e.g. to silence whole crates at once:
+/// ```ignore
+/// RerunComponentRules::new([
+///     RerunComponentRule::allow("bevy_render::view::visibility::ViewVisibility"),
+///     RerunComponentRule::deny("bevy_render::*"),
+/// ])
+/// ```
+///
+/// Rules are evaluated in order and the first one that matches wins. Components that no rule
+/// matches are allowed.
+///
+/// Precedence, from highest to lowest:
+/// 1. Loggers registered for that exact component, see [`crate::RerunTypedComponentLoggers`] and
+///    [`crate::RerunComponentLoggers`].
+/// 2. These rules: a denied component never gets synced.
+/// 3. [`crate::DefaultRerunComponentLoggers`].
+/// 4. [`crate::ReflectToRerun`].
+/// 5. The generic reflection-based logger.
+///
+/// Changing the rules at runtime only affects components as they change.
+#[derive(Resource, Debug, Default, Clone, Deref, DerefMut)]
+pub struct RerunComponentRules(pub Vec<RerunComponentRule>);
+
+impl RerunComponentRules {
+    pub fn new(rules: impl IntoIterator<Item = RerunComponentRule>) -> Self {
+        Self(rules.into_iter().collect())
+    }
+
+    pub fn is_allowed(&self, component_name: &str) -> bool {
+        self.iter()
+            .find(|rule| rule.matches(component_name))
+            .is_none_or(|rule| matches!(rule, RerunComponentRule::Allow(_)))
+    }
+}
+
+/// Matches `text` against `pattern`, where `*` matches any sequence of characters.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts = parts.collect::<Vec<_>>();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty(); // No wildcard at all.
+    };
+
+    for part in middle {
+        let Some(index) = rest.find(part) else {
+            return false;
+        };
+        rest = &rest[index + part.len()..];
+    }
+
+    rest.ends_with(last)
+}
+
+// ---
+
+/// The components that [`RerunComponentRules`] currently deny, computed once for every registered
+/// component rather than glob-matched every time one changes.
+#[derive(Default)]
+pub(crate) struct DeniedComponents {
+    denied: HashSet<ComponentId>,
+
+    /// How many components were registered the last time this was computed.
+    num_components: usize,
+}
+
+impl DeniedComponents {
+    /// Recomputes the denied components if the rules, the loggers or the registered components
+    /// changed since the last time.
+    pub fn update(&mut self, world: &World) {
+        let num_components = world.components().len();
+        let is_outdated = num_components != self.num_components
+            || world.is_resource_changed::<RerunComponentRules>()
+            || world.is_resource_changed::<RerunComponentLoggers>()
+            || world.is_resource_changed::<RerunTypedComponentLoggers>();
+        if !is_outdated {
+            return;
+        }
+
+        let _trace = info_span!("update_denied_components").entered();
+
+        self.num_components = num_components;
+        self.denied.clear();
+
+        let Some(rules) = world.get_resource::<RerunComponentRules>() else {
+            return;
+        };
+        let typed_loggers = world.get_resource::<RerunTypedComponentLoggers>();
+        let loggers = world.get_resource::<RerunComponentLoggers>();
+
+        self.denied.extend(
+            world
+                .components()
+                .iter_registered()
+                .filter(|component| {
+                    let has_logger =
+                        get_registered_logger(component, typed_loggers, loggers).is_some();
+                    !has_logger && !rules.is_allowed(component.name())
+                })
+                .map(|component| component.id()),
+        );
+    }
+
+    #[inline]
+    pub fn contains(&self, component_id: ComponentId) -> bool {
+        self.denied.contains(&component_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_without_wildcard_matches_exactly() {
+        assert!(glob_matches("bevy_pbr::Foo", "bevy_pbr::Foo"));
+        assert!(!glob_matches("bevy_pbr::Foo", "bevy_pbr::FooBar"));
+        assert!(!glob_matches("bevy_pbr::Foo", "my_bevy_pbr::Foo"));
+        assert!(!glob_matches("bevy_pbr::Foo", "bevy_pbr"));
+        assert!(glob_matches("", ""));
+        assert!(!glob_matches("", "bevy_pbr"));
+    }
+
+    #[test]
+    fn glob_leading_and_trailing_wildcards() {
+        assert!(glob_matches(
+            "bevy_render::*",
+            "bevy_render::view::Visibility"
+        ));
+        assert!(glob_matches("bevy_render::*", "bevy_render::"));
+        assert!(!glob_matches("bevy_render::*", "bevy_renderer::Foo"));
+
+        assert!(glob_matches(
+            "*::Visibility",
+            "bevy_render::view::Visibility"
+        ));
+        assert!(!glob_matches(
+            "*::Visibility",
+            "bevy_render::view::ViewVisibility"
+        ));
+
+        assert!(glob_matches("*Cascade*", "bevy_pbr::light::Cascades"));
+        assert!(glob_matches("*Cascade*", "Cascade"));
+        assert!(!glob_matches("*Cascade*", "bevy_pbr::light::Cascad"));
+
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("*", "anything::at::all"));
+        assert!(glob_matches("**", "anything"));
+    }
+
+    #[test]
+    fn glob_overlapping_parts() {
+        // The prefix and the suffix cannot share characters.
+        assert!(!glob_matches("ab*ba", "aba"));
+        assert!(glob_matches("ab*ba", "abba"));
+        assert!(glob_matches("ab*ba", "abxba"));
+
+        // Neither can a middle part and the suffix.
+        assert!(!glob_matches("*ab*b", "ab"));
+        assert!(glob_matches("*ab*b", "abb"));
+        assert!(!glob_matches("*a*a", "a"));
+        assert!(glob_matches("*a*a", "aa"));
+
+        // The earliest occurrence of a middle part leaves the most room for the rest.
+        assert!(glob_matches("*ab*ab", "xabab"));
+        assert!(glob_matches(
+            "bevy_pbr::*::*Cascade*",
+            "bevy_pbr::light::Cascades"
+        ));
+        assert!(!glob_matches(
+            "bevy_pbr::*::*Cascade*",
+            "bevy_pbr::Cascades"
+        ));
+    }
+}
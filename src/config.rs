@@ -0,0 +1,207 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    ecs::event::EventCursor,
+    platform::collections::HashMap,
+    prelude::*,
+};
+use serde::Deserialize;
+
+use crate::{
+    FlatPathStrategy, HierarchicalPathStrategy, NamePathStrategy, RerunComponentRule,
+    RerunComponentRules, RerunEntityPathStrategy, RerunLogger, RerunSyncRate, RerunSyncRates,
+    StablePathStrategy,
+};
+
+// ---
+
+/// The contents of a `revy.ron` file, see [`crate::RerunPlugin::with_config_file`].
+///
+/// Everything is optional: whatever is left out keeps being configured from code, including
+/// whatever gets removed from the file while the app is running.
+/// E.g.:
+/// ```ron
+/// (
+///     component_rules: [Deny("bevy_render::*"), Deny("bevy_pbr::*::*Cascade*")],
+///     sync_rates: (
+///         global: MaxHz(10.0),
+///         components: { "my_game::Health": Unlimited },
+///     ),
+///     entity_filter: (with: ["my_game::Player"], without: ["my_game::Hidden"]),
+///     path_strategy: Name,
+///     disabled_default_loggers: ["bevy_render::primitives::Aabb"],
+/// )
+/// ```
+#[derive(Asset, TypePath, Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RevyConfig {
+    /// Replaces [`crate::RerunComponentRules`].
+    pub component_rules: Option<Vec<RerunComponentRule>>,
+
+    /// Replaces [`crate::RerunSyncRates`].
+    pub sync_rates: Option<RevyConfigSyncRates>,
+
+    /// Only syncs the entities that match, on top of the filters configured from code.
+    pub entity_filter: Option<RevyConfigEntityFilter>,
+
+    /// Replaces [`crate::RerunEntityPathStrategy`].
+    pub path_strategy: Option<RevyConfigPathStrategy>,
+
+    /// The fully-qualified type paths of the components that shouldn't use their
+    /// [`crate::DefaultRerunComponentLoggers`], and fall back to the generic reflection-based
+    /// logger instead.
+    pub disabled_default_loggers: Vec<String>,
+}
+
+/// See [`crate::RerunSyncRates`].
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RevyConfigSyncRates {
+    pub global: RerunSyncRate,
+
+    /// Keyed by fully-qualified type path.
+    pub components: std::collections::HashMap<String, RerunSyncRate>,
+}
+
+/// Matches entities based on the fully-qualified type paths of their components.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RevyConfigEntityFilter {
+    /// Entities must have all of these.
+    pub with: Vec<String>,
+
+    /// Entities must have none of these.
+    pub without: Vec<String>,
+}
+
+/// The built-in [`crate::EntityPathStrategy`]s.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevyConfigPathStrategy {
+    /// See [`HierarchicalPathStrategy`].
+    Hierarchical,
+
+    /// See [`FlatPathStrategy`].
+    Flat,
+
+    /// See [`NamePathStrategy`].
+    Name,
+
+    /// See [`StablePathStrategy`].
+    Stable,
+}
+
+impl RevyConfigPathStrategy {
+    pub fn to_strategy(self) -> RerunEntityPathStrategy {
+        match self {
+            Self::Hierarchical => RerunEntityPathStrategy::new(HierarchicalPathStrategy),
+            Self::Flat => RerunEntityPathStrategy::new(FlatPathStrategy),
+            Self::Name => RerunEntityPathStrategy::new(NamePathStrategy::default()),
+            Self::Stable => RerunEntityPathStrategy::new(StablePathStrategy::default()),
+        }
+    }
+}
+
+// ---
+
+#[derive(Default)]
+pub(crate) struct RevyConfigLoader;
+
+impl AssetLoader for RevyConfigLoader {
+    type Asset = RevyConfig;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &Self::Settings,
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+
+        // Saves users from having to wrap everything in `Some(...)`.
+        let config = ron::Options::default()
+            .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+            .from_bytes(&bytes)?;
+
+        Ok(config)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["revy.ron"]
+    }
+}
+
+/// Keeps track of the config file and of whatever applying it changed, so it can be undone when
+/// the file changes.
+pub(crate) struct RevyConfigState {
+    pub path: String,
+    pub handle: Option<Handle<RevyConfig>>,
+    pub events: EventCursor<AssetEvent<RevyConfig>>,
+
+    /// The default loggers that were disabled by the file, so they can be restored.
+    pub disabled_default_loggers: HashMap<rerun::ComponentName, Option<RerunLogger>>,
+
+    /// The values configured from code for whatever the file currently replaces, so they can be
+    /// restored once it doesn't anymore.
+    pub code_component_rules: Option<RerunComponentRules>,
+    pub code_sync_rates: Option<RerunSyncRates>,
+    pub code_path_strategy: Option<RerunEntityPathStrategy>,
+}
+
+impl RevyConfigState {
+    pub fn new(path: String) -> Self {
+        Self {
+            path,
+            handle: None,
+            events: Default::default(),
+            disabled_default_loggers: Default::default(),
+            code_component_rules: None,
+            code_sync_rates: None,
+            code_path_strategy: None,
+        }
+    }
+
+    /// Inserts `value` if the file has it, and restores the value configured from code otherwise.
+    ///
+    /// `code_value` holds on to the value configured from code for as long as the file replaces it.
+    pub fn replace_resource<R: Resource + Clone>(
+        world: &mut World,
+        code_value: &mut Option<R>,
+        value: Option<R>,
+    ) {
+        match value {
+            Some(value) => {
+                if code_value.is_none() {
+                    *code_value = world.get_resource::<R>().cloned();
+                }
+                world.insert_resource(value);
+            }
+            None => {
+                if let Some(code_value) = code_value.take() {
+                    world.insert_resource(code_value);
+                }
+            }
+        }
+    }
+
+    /// Returns the contents of the config file if it was (re)loaded since the last call.
+    ///
+    /// Starts loading the file on the first call.
+    pub fn poll(&mut self, world: &World) -> Option<RevyConfig> {
+        if self.handle.is_none() {
+            let asset_server = world.get_resource::<AssetServer>()?;
+            self.handle = Some(asset_server.load(self.path.clone()));
+        }
+        let handle = self.handle.as_ref()?;
+
+        let events = world.get_resource::<Events<AssetEvent<RevyConfig>>>()?;
+        let is_modified = self.events.read(events).fold(false, |is_modified, event| {
+            is_modified || event.is_loaded_with_dependencies(handle) || event.is_modified(handle)
+        });
+
+        is_modified
+            .then(|| world.resource::<Assets<RevyConfig>>().get(handle).cloned())
+            .flatten()
+    }
+}
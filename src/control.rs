@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+
+// ---
+
+/// Pauses, resumes and restarts the sync at runtime.
+///
+/// Can be driven either directly through this resource, or by sending [`RerunControlEvent`]s.
+/// Either way, changes take effect at the next [`crate::RerunSamplePoint`].
+///
+/// Resuming and restarting both re-log a full snapshot of the database, so that the recording
+/// doesn't end up with a gap that only later changes would fill.
+#[derive(Resource, Debug, Default)]
+pub struct RerunControl {
+    paused: bool,
+    restart: Option<rerun::RecordingStream>,
+}
+
+impl RerunControl {
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    #[inline]
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    #[inline]
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    #[inline]
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Syncs into `rec` from now on, starting with a full snapshot.
+    ///
+    /// Whatever was already captured still goes to the previous recording. To start a fresh
+    /// recording, build `rec` with a new recording id, e.g.:
+    /// ```ignore
+    /// control.restart(
+    ///     RecordingStreamBuilder::new("my_app")
+    ///         .recording_id(uuid::Uuid::new_v4().to_string())
+    ///         .spawn()?,
+    /// );
+    /// ```
+    #[inline]
+    pub fn restart(&mut self, rec: rerun::RecordingStream) {
+        self.restart = Some(rec);
+    }
+
+    /// Returns the pending restart, if any.
+    #[inline]
+    pub(crate) fn take_restart(&mut self) -> Option<rerun::RecordingStream> {
+        self.restart.take()
+    }
+
+    pub(crate) fn apply(&mut self, event: &RerunControlEvent) {
+        match event {
+            RerunControlEvent::Pause => self.pause(),
+            RerunControlEvent::Resume => self.resume(),
+            RerunControlEvent::TogglePause => self.toggle_pause(),
+            RerunControlEvent::Restart(rec) => self.restart(rec.clone()),
+        }
+    }
+}
+
+/// Event-based interface to [`RerunControl`].
+#[derive(Event, Debug, Clone)]
+pub enum RerunControlEvent {
+    Pause,
+    Resume,
+    TogglePause,
+
+    /// See [`RerunControl::restart`].
+    Restart(rerun::RecordingStream),
+}
+
+/// Toggles [`RerunControl`] whenever `hotkey` is pressed.
+///
+/// See [`crate::RerunPlugin::with_pause_hotkey`].
+pub(crate) fn toggle_pause_on_hotkey(
+    hotkey: KeyCode,
+) -> impl FnMut(Option<Res<'_, ButtonInput<KeyCode>>>, ResMut<'_, RerunControl>) {
+    move |keys, mut control| {
+        if keys.is_some_and(|keys| keys.just_pressed(hotkey)) {
+            control.toggle_pause();
+            info!(paused = control.is_paused(), "toggled revy's sync");
+        }
+    }
+}
@@ -1,4 +1,9 @@
-use bevy::{math::Vec3A, prelude::*, render::mesh::VertexAttributeValues};
+use bevy::{
+    math::Vec3A,
+    prelude::*,
+    reflect::{FromType, ReflectRef, TypeRegistry},
+    render::mesh::VertexAttributeValues,
+};
 use itertools::Itertools;
 
 // ---
@@ -122,3 +127,122 @@ impl ToRerun<Option<rerun::archetypes::Mesh3D>> for Mesh {
         }
     }
 }
+
+// ---
+
+/// Whatever a type logs when converted through [`ReflectToRerun`].
+pub type RerunBatches = Vec<rerun::SerializedComponentBatch>;
+
+/// Type data to log any reflected type through its [`ToRerun`] conversion, without having to
+/// write a logger for it:
+/// ```ignore
+/// #[derive(Component, Reflect)]
+/// #[reflect(Component, ToRerun)]
+/// struct Health(f32);
+///
+/// impl ToRerun<RerunBatches> for Health {
+///     fn to_rerun(&self) -> RerunBatches {
+///         rerun::Scalars::single(self.0).as_serialized_batches()
+///     }
+/// }
+///
+/// app.register_type::<Health>();
+/// ```
+///
+/// The sync looks it up in the [`AppTypeRegistry`] for all the components that would otherwise
+/// be logged by the generic reflection-based logger:
+/// * If the component itself has it, its conversion is logged at the path of the entity, instead
+///   of the reflected component.
+/// * Otherwise, the conversions of all of its fields that have it, however deeply nested in
+///   structs, are logged alongside the reflected component, each at its own
+///   `<entity>/comps/<component>/<field path>`, e.g. `player/comps/game.Player/body/transform`.
+#[derive(Clone)]
+pub struct ReflectToRerun {
+    to_rerun: fn(&dyn PartialReflect) -> Option<RerunBatches>,
+}
+
+impl ReflectToRerun {
+    /// Converts `value`, or returns `None` if it isn't of the type this was registered for.
+    #[inline]
+    pub fn to_rerun(&self, value: &dyn PartialReflect) -> Option<RerunBatches> {
+        (self.to_rerun)(value)
+    }
+}
+
+impl<T: Reflect + ToRerun<RerunBatches>> FromType<T> for ReflectToRerun {
+    fn from_type() -> Self {
+        Self {
+            to_rerun: |value| value.try_downcast_ref::<T>().map(ToRerun::to_rerun),
+        }
+    }
+}
+
+/// What [`ReflectToRerun`] makes of a reflected value, see [`reflected_to_rerun`].
+pub(crate) enum ReflectedToRerun {
+    /// The value has its own conversion.
+    Whole(RerunBatches),
+
+    /// The value doesn't have its own conversion, but some of its fields do, along with their
+    /// paths within the value (e.g. `body/transform`).
+    Fields(Vec<(Vec<rerun::EntityPathPart>, RerunBatches)>),
+}
+
+/// Converts `value` using [`ReflectToRerun`], if it or any of its fields has it.
+pub(crate) fn reflected_to_rerun(
+    value: &dyn PartialReflect,
+    type_registry: &TypeRegistry,
+) -> Option<ReflectedToRerun> {
+    if let Some(batches) = to_rerun(value, type_registry) {
+        return Some(ReflectedToRerun::Whole(batches));
+    }
+
+    let mut fields = Vec::new();
+    fields_to_rerun(value, type_registry, &mut Vec::new(), &mut fields);
+
+    (!fields.is_empty()).then_some(ReflectedToRerun::Fields(fields))
+}
+
+fn to_rerun(value: &dyn PartialReflect, type_registry: &TypeRegistry) -> Option<RerunBatches> {
+    let type_id = value.get_represented_type_info()?.type_id();
+    type_registry
+        .get_type_data::<ReflectToRerun>(type_id)?
+        .to_rerun(value)
+}
+
+/// Converts all the fields of `value` that have [`ReflectToRerun`], recursively.
+///
+/// `field_path` is the path of `value` itself, within the value being converted.
+fn fields_to_rerun(
+    value: &dyn PartialReflect,
+    type_registry: &TypeRegistry,
+    field_path: &mut Vec<rerun::EntityPathPart>,
+    fields: &mut Vec<(Vec<rerun::EntityPathPart>, RerunBatches)>,
+) {
+    let named_fields = match value.reflect_ref() {
+        ReflectRef::Struct(value) => value
+            .iter_fields()
+            .enumerate()
+            .map(|(index, field)| {
+                let name = value
+                    .name_at(index)
+                    .map_or_else(|| index.to_string(), ToOwned::to_owned);
+                (name, field)
+            })
+            .collect::<Vec<_>>(),
+        ReflectRef::TupleStruct(value) => value
+            .iter_fields()
+            .enumerate()
+            .map(|(index, field)| (index.to_string(), field))
+            .collect::<Vec<_>>(),
+        _ => return,
+    };
+
+    for (name, field) in named_fields {
+        field_path.push(rerun::EntityPathPart::new(name));
+        match to_rerun(field, type_registry) {
+            Some(batches) => fields.push((field_path.clone(), batches)),
+            None => fields_to_rerun(field, type_registry, field_path, fields),
+        }
+        field_path.pop();
+    }
+}
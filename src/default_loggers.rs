@@ -1,8 +1,10 @@
-use bevy::{ecs::component::ComponentInfo, prelude::*, render::primitives::Aabb};
+use bevy::{prelude::*, render::primitives::Aabb};
 
 use rerun::{AsComponents as _, ComponentBatch, external::nohash_hasher::IntMap};
 
-use crate::{RerunLogger, ToRerun, compute_entity_path};
+use crate::{
+    RerunIgnore, RerunLogBuilder, RerunLogContext, RerunLogger, RerunRoot, RerunTrack, ToRerun,
+};
 
 // ---
 
@@ -31,19 +33,34 @@ impl Default for DefaultRerunComponentLoggers {
 
         loggers.insert(
             "bevy_render::primitives::Aabb".into(),
-            Some(RerunLogger::new_static(&bevy_aabb)),
+            Some(
+                RerunLogger::new_static(&bevy_aabb)
+                    .with_asset_dependency(|entity| {
+                        entity
+                            .get::<MeshMaterial2d<ColorMaterial>>()
+                            .map(|handle| handle.id())
+                    })
+                    .with_asset_dependency(|entity| {
+                        entity
+                            .get::<MeshMaterial3d<StandardMaterial>>()
+                            .map(|handle| handle.id())
+                    }),
+            ),
         );
 
         loggers.insert(
-            "bevy_hierarchy::components::ChildOf::ChildOf".into(),
+            std::any::type_name::<ChildOf>().into(),
             Some(RerunLogger::new_static(&bevy_child_of)),
         );
         loggers.insert(
-            "bevy_hierarchy::components::children::Children".into(),
+            std::any::type_name::<Children>().into(),
             Some(RerunLogger::new_static(&bevy_children)),
         );
 
         loggers.insert("revy::entity_path::RerunEntityPath".into(), None);
+        loggers.insert(std::any::type_name::<RerunIgnore>().into(), None);
+        loggers.insert(std::any::type_name::<RerunRoot>().into(), None);
+        loggers.insert(std::any::type_name::<RerunTrack>().into(), None);
 
         Self(loggers)
     }
@@ -54,35 +71,20 @@ impl Default for DefaultRerunComponentLoggers {
 // TODO(cmc): all those aliasing reshenanigans should really just be custom archetype names in
 // the descriptor, but the viewer won't be ready for that in 0.22.
 
-fn bevy_transform<'w>(
-    _world: &'w World,
-    _all_entities: &'w QueryState<(Entity, Option<&'w ChildOf>, Option<&'w Name>)>,
-    entity: EntityRef<'_>,
-    _component: &'w ComponentInfo,
-) -> (Option<&'static str>, Vec<rerun::SerializedComponentBatch>) {
-    (
-        None,
-        entity
+fn bevy_transform(ctx: &RerunLogContext<'_>) -> RerunLogBuilder {
+    RerunLogBuilder::new().with_batches(
+        ctx.entity()
             .get::<Transform>()
             .into_iter()
-            .flat_map(|transform| transform.to_rerun().as_serialized_batches())
-            .collect(),
+            .flat_map(|transform| transform.to_rerun().as_serialized_batches()),
     )
 }
 
-fn bevy_global_transform<'w>(
-    _world: &'w World,
-    _all_entities: &'w QueryState<(Entity, Option<&'w ChildOf>, Option<&'w Name>)>,
-    entity: EntityRef<'_>,
-    _component: &'w ComponentInfo,
-) -> (Option<&'static str>, Vec<rerun::SerializedComponentBatch>) {
-    let suffix = None;
+fn bevy_global_transform(ctx: &RerunLogContext<'_>) -> RerunLogBuilder {
     // TODO(cmc): once again the DataUi does the wrong thing... we really need to
     // go typeless.
-    let data = entity
-        .get::<GlobalTransform>()
-        .into_iter()
-        .flat_map(|transform| {
+    RerunLogBuilder::new().with_batches(ctx.entity().get::<GlobalTransform>().into_iter().flat_map(
+        |transform| {
             transform
                 .to_rerun()
                 .as_serialized_batches()
@@ -93,19 +95,12 @@ fn bevy_global_transform<'w>(
                         "{name}Global"
                     )))
                 })
-        })
-        .collect();
-
-    (suffix, data)
+        },
+    ))
 }
 
-fn bevy_aabb<'w>(
-    world: &'w World,
-    _all_entities: &'w QueryState<(Entity, Option<&'w ChildOf>, Option<&'w Name>)>,
-    entity: EntityRef<'_>,
-    _component: &'w ComponentInfo,
-) -> (Option<&'static str>, Vec<rerun::SerializedComponentBatch>) {
-    let suffix = Some("aabb");
+fn bevy_aabb(ctx: &RerunLogContext<'_>) -> RerunLogBuilder {
+    let entity = ctx.entity();
     let batches = entity
         .get::<Aabb>()
         .map(|aabb| {
@@ -117,12 +112,12 @@ fn bevy_aabb<'w>(
         .map(|aabb| {
             if let Some(mat) = entity
                 .get::<MeshMaterial2d<ColorMaterial>>()
-                .and_then(|handle| world.resource::<Assets<ColorMaterial>>().get(handle))
+                .and_then(|handle| ctx.asset(handle))
             {
                 aabb.with_colors([mat.color.to_rerun()])
             } else if let Some(mat) = entity
                 .get::<MeshMaterial3d<StandardMaterial>>()
-                .and_then(|handle| world.resource::<Assets<StandardMaterial>>().get(handle))
+                .and_then(|handle| ctx.asset(handle))
             {
                 aabb.with_colors([mat.base_color.to_rerun()])
             } else if let Some(sprite) = entity.get::<Sprite>() {
@@ -132,58 +127,29 @@ fn bevy_aabb<'w>(
             }
         })
         .into_iter()
-        .flat_map(|aabb| aabb.as_serialized_batches())
-        .collect();
-    (suffix, batches)
+        .flat_map(|aabb| aabb.as_serialized_batches());
+    RerunLogBuilder::new().with_batches_at("aabb", batches)
 }
 
-fn bevy_child_of<'w>(
-    world: &'w World,
-    all_entities: &'w QueryState<(Entity, Option<&'w ChildOf>, Option<&'w Name>)>,
-    entity: EntityRef<'_>,
-    _component: &'w ComponentInfo,
-) -> (Option<&'static str>, Vec<rerun::SerializedComponentBatch>) {
-    let suffix = None;
-    let batches = entity
-        .get::<ChildOf>()
-        .and_then(|child_of| {
-            let childof_entity_path = compute_entity_path(world, all_entities, child_of.parent());
-            rerun::components::EntityPath(childof_entity_path.to_string().into())
-                .serialized()
-                .map(|batch| {
-                    batch.with_descriptor_override(rerun::ComponentDescriptor::new("ChildOf"))
-                })
-        })
-        .into_iter()
-        .collect();
-    (suffix, batches)
+fn bevy_child_of(ctx: &RerunLogContext<'_>) -> RerunLogBuilder {
+    RerunLogBuilder::new().with_batches(ctx.entity().get::<ChildOf>().and_then(|child_of| {
+        let childof_entity_path = ctx.entity_path_of(child_of.parent());
+        rerun::components::EntityPath(childof_entity_path.to_string().into())
+            .serialized()
+            .map(|batch| batch.with_descriptor_override(rerun::ComponentDescriptor::new("ChildOf")))
+    }))
 }
 
-fn bevy_children<'w>(
-    world: &'w World,
-    all_entities: &'w QueryState<(Entity, Option<&'w ChildOf>, Option<&'w Name>)>,
-    entity: EntityRef<'_>,
-    _component: &'w ComponentInfo,
-) -> (Option<&'static str>, Vec<rerun::SerializedComponentBatch>) {
-    let suffix = None;
-    let batches = entity
-        .get::<Children>()
-        .and_then(|children| {
-            let children = children
-                .iter()
-                .map(|entity_id| {
-                    rerun::components::EntityPath(
-                        compute_entity_path(world, all_entities, entity_id)
-                            .to_string()
-                            .into(),
-                    )
-                })
-                .collect::<Vec<_>>();
-            children.serialized().map(|batch| {
-                batch.with_descriptor_override(rerun::ComponentDescriptor::new("Children"))
+fn bevy_children(ctx: &RerunLogContext<'_>) -> RerunLogBuilder {
+    RerunLogBuilder::new().with_batches(ctx.entity().get::<Children>().and_then(|children| {
+        let children = children
+            .iter()
+            .map(|entity_id| {
+                rerun::components::EntityPath(ctx.entity_path_of(entity_id).to_string().into())
             })
+            .collect::<Vec<_>>();
+        children.serialized().map(|batch| {
+            batch.with_descriptor_override(rerun::ComponentDescriptor::new("Children"))
         })
-        .into_iter()
-        .collect();
-    (suffix, batches)
+    }))
 }
@@ -1,4 +1,11 @@
-use bevy::prelude::*;
+use std::{marker::PhantomData, sync::Arc};
+
+use bevy::{
+    ecs::component::{ComponentId, Tick},
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+};
+use parking_lot::Mutex;
 
 // ---
 
@@ -24,10 +31,11 @@ pub fn ancestors_from_world<'w: 's, 's>(
     })
 }
 
-/// Computes the [`rerun::EntityPath`] of the specified `entity_id`.
+/// Computes the [`rerun::EntityPath`] of the specified `entity_id`, according to the current
+/// [`RerunEntityPathStrategy`] and under its [`RerunRoot`], or the current [`RerunEntityPathRoot`].
 ///
-/// The entity path is hierarchy dependent: if the target entity's parent change, the next call to
-/// this function will yield a different result.
+/// With the default strategy, the entity path is hierarchy dependent: if the target entity's parent
+/// change, the next call to this function will yield a different result.
 ///
 /// `entities` must have been updated manually before calling this function, or the results will be
 /// out-of-date.
@@ -36,26 +44,511 @@ pub fn compute_entity_path<'w: 's, 's>(
     entities: &'w QueryState<(Entity, Option<&'s ChildOf>, Option<&'s Name>)>,
     entity_id: Entity,
 ) -> rerun::EntityPath {
-    // TODO(cmc): kinda awkward that we have to prefix `world/` everywhere or hell ensues.
-    std::iter::once(rerun::EntityPathPart::new("world"))
-        .chain(
-            std::iter::once(entity_id)
-                .chain(ancestors_from_world(world, entities, entity_id))
-                .map(|entity_id| {
-                    rerun::EntityPathPart::new({
-                        entities
-                            .get_manual(world, entity_id)
-                            .ok()
-                            .and_then(|(_, _, name)| name)
-                            .map_or_else(
-                                || format!("{entity_id:?}"),
-                                |name| format!("{entity_id:?}_{name}"),
-                            )
-                    })
+    let entity_path = match world.get_resource::<RerunEntityPathStrategy>() {
+        Some(strategy) => strategy.entity_path(world, entities, entity_id),
+        None => HierarchicalPathStrategy.entity_path(world, entities, entity_id),
+    };
+
+    join_root(world, entities, entity_id, &entity_path)
+}
+
+/// Same as [`compute_entity_path`], for entities that aren't synced, see
+/// [`EntityPathStrategy::peek_entity_path`].
+pub(crate) fn peek_entity_path<'w: 's, 's>(
+    world: &'w World,
+    entities: &'w QueryState<(Entity, Option<&'s ChildOf>, Option<&'s Name>)>,
+    entity_id: Entity,
+) -> rerun::EntityPath {
+    let entity_path = match world.get_resource::<RerunEntityPathStrategy>() {
+        Some(strategy) => strategy.peek_entity_path(world, entities, entity_id),
+        None => HierarchicalPathStrategy.entity_path(world, entities, entity_id),
+    };
+
+    join_root(world, entities, entity_id, &entity_path)
+}
+
+/// Prefixes `entity_path` with the [`RerunRoot`] of `entity_id` or of its nearest ancestor that
+/// has one, or with the [`RerunEntityPathRoot`] otherwise.
+fn join_root<'w: 's, 's>(
+    world: &'w World,
+    entities: &'w QueryState<(Entity, Option<&'s ChildOf>, Option<&'s Name>)>,
+    entity_id: Entity,
+    entity_path: &rerun::EntityPath,
+) -> rerun::EntityPath {
+    let root = std::iter::once(entity_id)
+        .chain(ancestors_from_world(world, entities, entity_id))
+        .find_map(|entity_id| world.get::<RerunRoot>(entity_id));
+    if let Some(root) = root {
+        return root.join(entity_path);
+    }
+
+    match world.get_resource::<RerunEntityPathRoot>() {
+        Some(root) => root.join(entity_path),
+        None => RerunEntityPathRoot::default().join(entity_path),
+    }
+}
+
+/// Where the synced entities end up in the recording, `world` by default, unless they have a
+/// [`RerunRoot`].
+///
+/// The [`crate::RerunPlugin::with_view_coordinates`] are logged there.
+/// Changing it at runtime re-logs everything under the new root, and clears the view coordinates
+/// of the old one.
+#[derive(Resource, Debug, Clone, PartialEq, Eq, Deref)]
+pub struct RerunEntityPathRoot(pub rerun::EntityPath);
+
+impl Default for RerunEntityPathRoot {
+    fn default() -> Self {
+        Self("world".into())
+    }
+}
+
+/// Syncs an entity and all its descendants under their own root, instead of the
+/// [`RerunEntityPathRoot`].
+///
+/// E.g. to keep 2D and 3D content apart, so that they end up in different views:
+/// ```ignore
+/// commands.spawn((Name::new("hud"), RerunRoot("world2d".into())));
+/// ```
+///
+/// The nearest root up the hierarchy wins. The [`crate::RerunPlugin::with_view_coordinates`] are
+/// logged at every root that is in use, and cleared once it isn't anymore.
+#[derive(Component, Debug, Clone, PartialEq, Eq, Deref)]
+pub struct RerunRoot(pub rerun::EntityPath);
+
+// ---
+
+/// Decides where entities end up in the recording, see [`RerunEntityPathStrategy`].
+pub trait EntityPathStrategy: Send + Sync + 'static {
+    /// Computes the [`rerun::EntityPath`] of `entity_id`, relative to the [`RerunEntityPathRoot`].
+    ///
+    /// This is called whenever an entity is synced for the first time, whenever one of the
+    /// [`Self::path_components`] of the entity or of one of its ancestors changes, as well as by
+    /// loggers that refer to other entities. It can be called from any thread.
+    fn entity_path<'w: 's, 's>(
+        &self,
+        world: &'w World,
+        entities: &'w QueryState<(Entity, Option<&'s ChildOf>, Option<&'s Name>)>,
+        entity_id: Entity,
+    ) -> rerun::EntityPath;
+
+    /// The components whose changes might affect the path of an entity, and of its descendants.
+    fn path_components(&self, world: &World) -> Vec<ComponentId> {
+        [
+            world.component_id::<ChildOf>(),
+            world.component_id::<Name>(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// Whether the path of an entity depends on its siblings, in which case all the children of
+    /// a parent get their paths recomputed whenever one of them changes.
+    fn depends_on_siblings(&self) -> bool {
+        false
+    }
+
+    /// Same as [`Self::entity_path`], for entities that aren't synced, e.g. when a logger refers
+    /// to one of them: whatever gets returned must not be kept track of, since [`Self::forget`]
+    /// will never be called for these entities.
+    fn peek_entity_path<'w: 's, 's>(
+        &self,
+        world: &'w World,
+        entities: &'w QueryState<(Entity, Option<&'s ChildOf>, Option<&'s Name>)>,
+        entity_id: Entity,
+    ) -> rerun::EntityPath {
+        self.entity_path(world, entities, entity_id)
+    }
+
+    /// Called once an entity stops being synced, e.g. because it was despawned.
+    fn forget(&self, _entity_id: Entity) {}
+}
+
+/// The [`EntityPathStrategy`] used by the sync, the clearing of despawned entities and the default
+/// `ChildOf`/`Children` loggers.
+///
+/// Defaults to [`HierarchicalPathStrategy`]. Changing it at runtime re-logs everything under the
+/// new paths.
+#[derive(Resource, Clone, Deref)]
+pub struct RerunEntityPathStrategy(pub Arc<dyn EntityPathStrategy>);
+
+impl Default for RerunEntityPathStrategy {
+    fn default() -> Self {
+        Self::new(HierarchicalPathStrategy)
+    }
+}
+
+impl RerunEntityPathStrategy {
+    pub fn new(strategy: impl EntityPathStrategy) -> Self {
+        Self(Arc::new(strategy))
+    }
+}
+
+/// Formats an entity path part for `entity_id`, optionally named.
+fn entity_path_part(entity_id: Entity, name: Option<&Name>) -> rerun::EntityPathPart {
+    rerun::EntityPathPart::new(name.map_or_else(
+        || format!("{entity_id:?}"),
+        |name| format!("{entity_id:?}_{name}"),
+    ))
+}
+
+/// `<grand-parent>/<parent>/<entity>`, where every part is formatted as `<entity>_<name>`,
+/// or just `<entity>` for unnamed entities.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HierarchicalPathStrategy;
+
+impl EntityPathStrategy for HierarchicalPathStrategy {
+    fn entity_path<'w: 's, 's>(
+        &self,
+        world: &'w World,
+        entities: &'w QueryState<(Entity, Option<&'s ChildOf>, Option<&'s Name>)>,
+        entity_id: Entity,
+    ) -> rerun::EntityPath {
+        std::iter::once(entity_id)
+            .chain(ancestors_from_world(world, entities, entity_id))
+            .map(|entity_id| {
+                let name = entities
+                    .get_manual(world, entity_id)
+                    .ok()
+                    .and_then(|(_, _, name)| name);
+                entity_path_part(entity_id, name)
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect()
+    }
+}
+
+/// `<entity>`, regardless of names and hierarchy.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FlatPathStrategy;
+
+impl EntityPathStrategy for FlatPathStrategy {
+    fn entity_path<'w: 's, 's>(
+        &self,
+        _world: &'w World,
+        _entities: &'w QueryState<(Entity, Option<&'s ChildOf>, Option<&'s Name>)>,
+        entity_id: Entity,
+    ) -> rerun::EntityPath {
+        std::iter::once(entity_path_part(entity_id, None)).collect()
+    }
+
+    fn path_components(&self, _world: &World) -> Vec<ComponentId> {
+        Vec::new()
+    }
+}
+
+/// `<name>`, regardless of hierarchy, or `<entity>` for unnamed entities.
+///
+/// Entities that share a name get `_2`, `_3`, etc appended to it, first come first served. An entity
+/// keeps its path for as long as it keeps its name, even if whoever first claimed that name is gone.
+///
+/// The sync claims names for new entities one at a time, in [`Entity`] index order, so that the
+/// same entities get the same paths from one run of a deterministic app to the next.
+#[derive(Debug, Default)]
+pub struct NamePathStrategy {
+    claims: Mutex<NameClaims>,
+}
+
+#[derive(Debug, Default)]
+struct NameClaims {
+    /// The name of every entity, and the path part it claimed.
+    entities: HashMap<Entity, (String, String)>,
+
+    /// All the path parts that are currently claimed.
+    claimed: HashSet<String>,
+}
+
+impl NameClaims {
+    /// The path part currently claimed by `entity_id`, if it still has the same `name`.
+    fn claim_of(&self, entity_id: Entity, name: &str) -> Option<String> {
+        self.entities
+            .get(&entity_id)
+            .filter(|(claimed_name, _)| claimed_name == name)
+            .map(|(_, part)| part.clone())
+    }
+
+    /// The path part that an entity named `name` would claim.
+    fn first_unclaimed(&self, name: &str) -> String {
+        std::iter::once(name.to_owned())
+            .chain((2..).map(|n| format!("{name}_{n}")))
+            .find(|part| !self.claimed.contains(part))
+            .unwrap_or_default()
+    }
+}
+
+impl NamePathStrategy {
+    fn name_of<'w: 's, 's>(
+        world: &'w World,
+        entities: &'w QueryState<(Entity, Option<&'s ChildOf>, Option<&'s Name>)>,
+        entity_id: Entity,
+    ) -> String {
+        entities
+            .get_manual(world, entity_id)
+            .ok()
+            .and_then(|(_, _, name)| name)
+            .map_or_else(|| format!("{entity_id:?}"), |name| name.to_string())
+    }
+}
+
+impl EntityPathStrategy for NamePathStrategy {
+    fn entity_path<'w: 's, 's>(
+        &self,
+        world: &'w World,
+        entities: &'w QueryState<(Entity, Option<&'s ChildOf>, Option<&'s Name>)>,
+        entity_id: Entity,
+    ) -> rerun::EntityPath {
+        let name = Self::name_of(world, entities, entity_id);
+
+        let mut claims = self.claims.lock();
+        let part = match claims.claim_of(entity_id, &name) {
+            Some(part) => part,
+            None => {
+                if let Some((_, part)) = claims.entities.remove(&entity_id) {
+                    claims.claimed.remove(&part);
+                }
+
+                let part = claims.first_unclaimed(&name);
+                claims.claimed.insert(part.clone());
+                claims.entities.insert(entity_id, (name, part.clone()));
+                part
+            }
+        };
+
+        std::iter::once(rerun::EntityPathPart::new(part)).collect()
+    }
+
+    fn peek_entity_path<'w: 's, 's>(
+        &self,
+        world: &'w World,
+        entities: &'w QueryState<(Entity, Option<&'s ChildOf>, Option<&'s Name>)>,
+        entity_id: Entity,
+    ) -> rerun::EntityPath {
+        let name = Self::name_of(world, entities, entity_id);
+
+        let claims = self.claims.lock();
+        let part = claims
+            .claim_of(entity_id, &name)
+            .unwrap_or_else(|| claims.first_unclaimed(&name));
+
+        std::iter::once(rerun::EntityPathPart::new(part)).collect()
+    }
+
+    fn path_components(&self, world: &World) -> Vec<ComponentId> {
+        world.component_id::<Name>().into_iter().collect()
+    }
+
+    fn forget(&self, entity_id: Entity) {
+        let mut claims = self.claims.lock();
+        if let Some((_, part)) = claims.entities.remove(&entity_id) {
+            claims.claimed.remove(&part);
+        }
+    }
+}
+
+/// `<grand-parent>/<parent>/<entity>`, where every part is the [`Name`] of the entity
+/// (`entity` for unnamed ones), followed by `[n]` for the n-th of the siblings that share that name.
+///
+/// Unlike [`HierarchicalPathStrategy`], nothing here depends on [`Entity`] ids, so that two runs of
+/// a deterministic app yield identical paths and their recordings can be compared.
+///
+/// Siblings are ordered as in the [`Children`] of their parent, i.e. in spawn order unless they
+/// were explicitly re-ordered. Root entities have no such thing: they get numbered as they show up
+/// instead, by [`Entity`] index among the ones that show up at the same time, and keep their
+/// number for as long as they keep their name. Numbers are never reused, so that roots never get
+/// renumbered (nor collide) as others come and go.
+#[derive(Debug, Default)]
+pub struct StablePathStrategy {
+    roots: Mutex<RootEntities>,
+}
+
+/// The numbers given to root entities, see [`StablePathStrategy`].
+#[derive(Debug, Default)]
+struct RootEntities {
+    /// When roots were last looked for, since that means going through all entities.
+    change_tick: Option<Tick>,
+
+    /// The name of every root entity, and its number among the roots that share that name.
+    numbers: HashMap<Entity, (String, usize)>,
+
+    /// How many roots were ever given each name.
+    counts: HashMap<String, usize>,
+}
+
+impl RootEntities {
+    fn number_of(&self, entity_id: Entity, name: &str) -> Option<usize> {
+        self.numbers
+            .get(&entity_id)
+            .filter(|(root_name, _)| root_name == name)
+            .map(|(_, number)| *number)
+    }
+
+    fn assign(&mut self, entity_id: Entity, name: String) -> usize {
+        let count = self.counts.entry(name.clone()).or_default();
+        let number = *count;
+        *count += 1;
+        self.numbers.insert(entity_id, (name, number));
+        number
+    }
+}
+
+impl StablePathStrategy {
+    fn entity_path_part<'w: 's, 's>(
+        &self,
+        world: &'w World,
+        entities: &'w QueryState<(Entity, Option<&'s ChildOf>, Option<&'s Name>)>,
+        entity_id: Entity,
+    ) -> rerun::EntityPathPart {
+        let name_of = |entity_id: Entity| {
+            entities
+                .get_manual(world, entity_id)
+                .ok()
+                .and_then(|(_, _, name)| name)
+                .map_or("entity", |name| name.as_str())
+        };
+        let name = name_of(entity_id);
+
+        let index = match world.get::<ChildOf>(entity_id) {
+            Some(child_of) => world
+                .get::<Children>(child_of.parent())
+                .map_or(0, |children| {
+                    children
+                        .iter()
+                        .take_while(|&sibling| sibling != entity_id)
+                        .filter(|&sibling| name_of(sibling) == name)
+                        .count()
+                }),
+            None => self.root_index(world, entities, entity_id, name),
+        };
+
+        rerun::EntityPathPart::new(if index == 0 {
+            name.to_owned()
+        } else {
+            format!("{name}[{index}]")
+        })
+    }
+
+    fn root_index<'w: 's, 's>(
+        &self,
+        world: &'w World,
+        entities: &'w QueryState<(Entity, Option<&'s ChildOf>, Option<&'s Name>)>,
+        entity_id: Entity,
+        name: &str,
+    ) -> usize {
+        let mut roots = self.roots.lock();
+        if let Some(number) = roots.number_of(entity_id, name) {
+            return number;
+        }
+
+        // Number all the new roots at once, in a deterministic order, rather than in whatever
+        // order they happen to be looked up in.
+        let change_tick = world.read_change_tick();
+        if roots.change_tick != Some(change_tick) {
+            roots.change_tick = Some(change_tick);
+            roots
+                .numbers
+                .retain(|&root_id, _| world.entities().contains(root_id));
+
+            let mut new_roots = entities
+                .iter_manual(world)
+                .filter(|(_, child_of, _)| child_of.is_none())
+                .map(|(root_id, _, root_name)| {
+                    (root_id, root_name.map_or("entity", |name| name.as_str()))
                 })
-                .collect::<Vec<_>>()
-                .into_iter()
-                .rev(),
-        )
+                .filter(|&(root_id, root_name)| roots.number_of(root_id, root_name).is_none())
+                .map(|(root_id, root_name)| (root_id, root_name.to_owned()))
+                .collect::<Vec<_>>();
+            new_roots.sort_by_key(|(root_id, _)| root_id.index());
+
+            for (root_id, root_name) in new_roots {
+                roots.assign(root_id, root_name);
+            }
+        }
+
+        match roots.number_of(entity_id, name) {
+            Some(number) => number,
+            None => roots.assign(entity_id, name.to_owned()),
+        }
+    }
+}
+
+impl EntityPathStrategy for StablePathStrategy {
+    fn entity_path<'w: 's, 's>(
+        &self,
+        world: &'w World,
+        entities: &'w QueryState<(Entity, Option<&'s ChildOf>, Option<&'s Name>)>,
+        entity_id: Entity,
+    ) -> rerun::EntityPath {
+        std::iter::once(entity_id)
+            .chain(ancestors_from_world(world, entities, entity_id))
+            .map(|entity_id| self.entity_path_part(world, entities, entity_id))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect()
+    }
+
+    /// Siblings get renumbered whenever one of them comes or goes, hence [`Children`].
+    fn path_components(&self, world: &World) -> Vec<ComponentId> {
+        [
+            world.component_id::<ChildOf>(),
+            world.component_id::<Name>(),
+            world.component_id::<Children>(),
+        ]
+        .into_iter()
+        .flatten()
         .collect()
+    }
+
+    /// Renaming an entity can renumber the siblings that share either of its names.
+    fn depends_on_siblings(&self) -> bool {
+        true
+    }
+
+    fn forget(&self, entity_id: Entity) {
+        self.roots.lock().numbers.remove(&entity_id);
+    }
+}
+
+/// `<path>`, where `<path>` is read from a user-defined component `C`.
+///
+/// Entities without a `C` component fall back to [`HierarchicalPathStrategy`].
+/// E.g.:
+/// ```ignore
+/// RerunEntityPathStrategy::new(ComponentPathStrategy::new(|path: &MyPath| path.0.as_str().into()))
+/// ```
+pub struct ComponentPathStrategy<C: Component> {
+    path: Box<dyn Fn(&C) -> rerun::EntityPath + Send + Sync>,
+    _component: PhantomData<fn(&C)>,
+}
+
+impl<C: Component> ComponentPathStrategy<C> {
+    pub fn new(path: impl Fn(&C) -> rerun::EntityPath + Send + Sync + 'static) -> Self {
+        Self {
+            path: Box::new(path),
+            _component: PhantomData,
+        }
+    }
+}
+
+impl<C: Component> EntityPathStrategy for ComponentPathStrategy<C> {
+    fn entity_path<'w: 's, 's>(
+        &self,
+        world: &'w World,
+        entities: &'w QueryState<(Entity, Option<&'s ChildOf>, Option<&'s Name>)>,
+        entity_id: Entity,
+    ) -> rerun::EntityPath {
+        match world.get::<C>(entity_id) {
+            Some(component) => (self.path)(component),
+            None => HierarchicalPathStrategy.entity_path(world, entities, entity_id),
+        }
+    }
+
+    fn path_components(&self, world: &World) -> Vec<ComponentId> {
+        let mut components = HierarchicalPathStrategy.path_components(world);
+        components.extend(world.component_id::<C>());
+        components
+    }
 }
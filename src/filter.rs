@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use bevy::{
+    ecs::{
+        component::ComponentId,
+        query::{QueryFilter, QueryState},
+    },
+    prelude::*,
+};
+
+use crate::config::RevyConfigEntityFilter;
+
+// ---
+
+/// Entities with this component never get synced, no matter what.
+///
+/// Takes precedence over [`RerunTrack`].
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component, Default)]
+pub struct RerunIgnore;
+
+/// Marks an entity as synced when the plugin is in opt-in mode, see
+/// [`crate::RerunPlugin::with_opt_in_tracking`].
+///
+/// Does nothing otherwise.
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component, Default)]
+pub struct RerunTrack;
+
+// ---
+
+/// Creates a type-erased [`QueryFilter`], see [`crate::RerunPlugin::with_entity_filter`].
+pub(crate) type NewEntityFilter = Arc<dyn Fn(&mut World) -> Box<dyn EntityFilter> + Send + Sync>;
+
+pub(crate) fn new_entity_filter<F: QueryFilter + 'static>() -> NewEntityFilter {
+    Arc::new(|world: &mut World| Box::new(QueryState::<(), F>::new(world)))
+}
+
+/// Type-erased [`QueryFilter`].
+pub(crate) trait EntityFilter: Send + Sync {
+    /// Must be called before [`Self::matches`] whenever archetypes might have changed.
+    fn update(&mut self, world: &World);
+
+    fn matches(&self, world: &World, entity_id: Entity) -> bool;
+}
+
+impl<F: QueryFilter + 'static> EntityFilter for QueryState<(), F> {
+    fn update(&mut self, world: &World) {
+        self.update_archetypes(world);
+    }
+
+    fn matches(&self, world: &World, entity_id: Entity) -> bool {
+        self.get_manual(world, entity_id).is_ok()
+    }
+}
+
+/// Matches entities based on the type paths of their components, see
+/// [`crate::RevyConfig::entity_filter`].
+pub(crate) struct ComponentNamesFilter {
+    config: RevyConfigEntityFilter,
+
+    /// Components get registered lazily, so the names are resolved again whenever new ones show up.
+    num_components: usize,
+    with: Vec<Option<ComponentId>>,
+    without: Vec<Option<ComponentId>>,
+}
+
+impl ComponentNamesFilter {
+    pub fn new(config: RevyConfigEntityFilter) -> Self {
+        Self {
+            config,
+            num_components: 0,
+            with: Vec::new(),
+            without: Vec::new(),
+        }
+    }
+}
+
+impl EntityFilter for ComponentNamesFilter {
+    fn update(&mut self, world: &World) {
+        let components = world.components();
+        if components.len() == self.num_components {
+            return;
+        }
+        self.num_components = components.len();
+
+        let resolve = |name: &String| {
+            components
+                .iter_registered()
+                .find(|component| component.name() == name.as_str())
+                .map(|component| component.id())
+        };
+        self.with = self.config.with.iter().map(resolve).collect();
+        self.without = self.config.without.iter().map(resolve).collect();
+    }
+
+    fn matches(&self, world: &World, entity_id: Entity) -> bool {
+        let Ok(entity) = world.get_entity(entity_id) else {
+            return false;
+        };
+
+        // Components that were never registered cannot possibly be there.
+        self.with
+            .iter()
+            .all(|component_id| component_id.is_some_and(|id| entity.contains_id(id)))
+            && !self
+                .without
+                .iter()
+                .flatten()
+                .any(|&component_id| entity.contains_id(component_id))
+    }
+}
+
+/// Decides which entities get synced at all.
+///
+/// Entities that don't pass the filters are invisible to the sync: no paths, no hashes, no logs.
+#[derive(Default)]
+pub(crate) struct EntityFilters {
+    /// Only sync entities that have a [`RerunTrack`] component.
+    pub opt_in: bool,
+
+    /// All of these must match.
+    pub filters: Vec<Box<dyn EntityFilter>>,
+
+    /// Must match too, if set. See [`crate::RevyConfig::entity_filter`].
+    pub config: Option<ComponentNamesFilter>,
+}
+
+impl EntityFilters {
+    pub fn update(&mut self, world: &World) {
+        for filter in &mut self.filters {
+            filter.update(world);
+        }
+        if let Some(filter) = &mut self.config {
+            filter.update(world);
+        }
+    }
+
+    pub fn is_tracked(&self, world: &World, entity: EntityRef<'_>) -> bool {
+        if entity.contains::<RerunIgnore>() {
+            return false;
+        }
+
+        if self.opt_in && !entity.contains::<RerunTrack>() {
+            return false;
+        }
+
+        self.filters
+            .iter()
+            .all(|filter| filter.matches(world, entity.id()))
+            && self
+                .config
+                .as_ref()
+                .is_none_or(|filter| filter.matches(world, entity.id()))
+    }
+}
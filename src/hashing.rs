@@ -0,0 +1,185 @@
+use std::hash::Hasher as _;
+
+use ahash::AHasher;
+use bevy::reflect::{PartialReflect, ReflectRef, TypeRegistry, serde::TypedReflectSerializer};
+
+// ---
+
+/// Computes a hash of a reflected value by walking its structure directly.
+///
+/// Types that provide [`PartialReflect::reflect_hash`] get to use that instead. Floats are hashed
+/// using their exact bit patterns.
+///
+/// Opaque types that neither can be hashed nor are floats have no choice but to be serialized.
+/// Returns `None` if that fails too.
+pub fn hash_reflected(value: &dyn PartialReflect, type_registry: &TypeRegistry) -> Option<u64> {
+    let mut hasher = AHasher::default();
+    hash_value(value, type_registry, &mut hasher)?;
+    Some(hasher.finish())
+}
+
+fn hash_value(
+    value: &dyn PartialReflect,
+    type_registry: &TypeRegistry,
+    hasher: &mut AHasher,
+) -> Option<()> {
+    if let Some(hash) = value.reflect_hash() {
+        hasher.write_u64(hash);
+        return Some(());
+    }
+
+    match value.reflect_ref() {
+        ReflectRef::Struct(value) => {
+            for field in value.iter_fields() {
+                hash_value(field, type_registry, hasher)?;
+            }
+        }
+
+        ReflectRef::TupleStruct(value) => {
+            for field in value.iter_fields() {
+                hash_value(field, type_registry, hasher)?;
+            }
+        }
+
+        ReflectRef::Tuple(value) => {
+            for field in value.iter_fields() {
+                hash_value(field, type_registry, hasher)?;
+            }
+        }
+
+        ReflectRef::List(value) => {
+            hasher.write_usize(value.len());
+            for item in value.iter() {
+                hash_value(item, type_registry, hasher)?;
+            }
+        }
+
+        ReflectRef::Array(value) => {
+            hasher.write_usize(value.len());
+            for item in value.iter() {
+                hash_value(item, type_registry, hasher)?;
+            }
+        }
+
+        // NOTE: Maps and sets don't guarantee any iteration order, so their entries are hashed
+        // independently and then combined in an order-independent way.
+        ReflectRef::Map(value) => {
+            hasher.write_usize(value.len());
+            let mut combined = 0u64;
+            for (key, value) in value.iter() {
+                let mut entry_hasher = AHasher::default();
+                hash_value(key, type_registry, &mut entry_hasher)?;
+                hash_value(value, type_registry, &mut entry_hasher)?;
+                combined = combined.wrapping_add(entry_hasher.finish());
+            }
+            hasher.write_u64(combined);
+        }
+
+        ReflectRef::Set(value) => {
+            hasher.write_usize(value.len());
+            let mut combined = 0u64;
+            for item in value.iter() {
+                let mut item_hasher = AHasher::default();
+                hash_value(item, type_registry, &mut item_hasher)?;
+                combined = combined.wrapping_add(item_hasher.finish());
+            }
+            hasher.write_u64(combined);
+        }
+
+        ReflectRef::Enum(value) => {
+            hasher.write_usize(value.variant_index());
+            for field in value.iter_fields() {
+                hash_value(field.value(), type_registry, hasher)?;
+            }
+        }
+
+        ReflectRef::Opaque(value) => hash_opaque(value, type_registry, hasher)?,
+
+        #[allow(unreachable_patterns)] // only reachable with `reflect_functions`
+        _ => return None,
+    }
+
+    Some(())
+}
+
+fn hash_opaque(
+    value: &dyn PartialReflect,
+    type_registry: &TypeRegistry,
+    hasher: &mut AHasher,
+) -> Option<()> {
+    if let Some(value) = value.try_downcast_ref::<f32>() {
+        hasher.write_u32(value.to_bits());
+    } else if let Some(value) = value.try_downcast_ref::<f64>() {
+        hasher.write_u64(value.to_bits());
+    } else {
+        // TODO(cmc): this is the expensive path, but it should only ever be hit by the odd leaf
+        // value rather than entire components.
+        let serializer = TypedReflectSerializer::new(value, type_registry);
+        let serialized = ron::ser::to_string(&serializer).ok()?;
+        hasher.write(serialized.as_bytes());
+    }
+
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::reflect::{DynamicMap, Reflect};
+
+    use super::*;
+
+    #[derive(Reflect)]
+    struct Sample {
+        position: f32,
+        history: Vec<f32>,
+    }
+
+    fn hash(value: &dyn PartialReflect) -> u64 {
+        hash_reflected(value, &TypeRegistry::new()).unwrap()
+    }
+
+    #[test]
+    fn hashes_follow_values() {
+        let a = Sample {
+            position: 1.0,
+            history: vec![1.0, 2.0],
+        };
+        let b = Sample {
+            position: 1.0,
+            history: vec![1.0, 2.0],
+        };
+        let c = Sample {
+            position: 1.5,
+            history: vec![1.0, 2.0],
+        };
+        assert_eq!(hash(&a), hash(&b));
+        assert_ne!(hash(&a), hash(&c));
+    }
+
+    #[test]
+    fn lists_are_ordered() {
+        assert_ne!(hash(&vec![1.0f32, 2.0]), hash(&vec![2.0f32, 1.0]));
+        assert_ne!(hash(&vec![1.0f32]), hash(&vec![1.0f32, 1.0]));
+    }
+
+    #[test]
+    fn floats_are_hashed_bitwise() {
+        assert_ne!(hash(&0.0f32), hash(&-0.0f32));
+        assert_eq!(hash(&f64::NAN), hash(&f64::NAN));
+    }
+
+    #[test]
+    fn maps_are_unordered() {
+        // `DynamicMap` iterates in insertion order.
+        let a = DynamicMap::from_iter([(1u32, 10.0f32), (2u32, 20.0f32), (3u32, 30.0f32)]);
+        let b = DynamicMap::from_iter([(3u32, 30.0f32), (1u32, 10.0f32), (2u32, 20.0f32)]);
+        assert_eq!(hash(&a), hash(&b));
+
+        // Swapping values between keys is still a change.
+        let c = DynamicMap::from_iter([(1u32, 20.0f32), (2u32, 10.0f32), (3u32, 30.0f32)]);
+        assert_ne!(hash(&a), hash(&c));
+
+        let d = DynamicMap::from_iter([(1u32, 10.0f32), (2u32, 20.0f32)]);
+        assert_ne!(hash(&a), hash(&d));
+    }
+}
@@ -2,38 +2,209 @@
 #![allow(clippy::doc_markdown)]
 #![doc = include_str!("../README.md")]
 
-use bevy::prelude::*;
+use bevy::{ecs::query::QueryFilter, prelude::*};
+
+use self::filter::{NewEntityFilter, new_entity_filter};
 
 // ---
 
 // TODO(cmc): support for bug report mode (buffering + kickoff)
 
 pub struct RerunPlugin {
-    pub rec: RecordingStream,
+    rec: RecordingStream,
+    sample_points: Vec<RerunSamplePoint>,
+    worker_queue_capacity: usize,
+    log_stats: bool,
+    opt_in: bool,
+    entity_filters: Vec<NewEntityFilter>,
+    pause_hotkey: Option<KeyCode>,
+    config_file: Option<String>,
+    path_strategy: Option<RerunEntityPathStrategy>,
+    path_root: Option<RerunEntityPathRoot>,
+    view_coordinates: Option<rerun::ViewCoordinates>,
+}
+
+impl RerunPlugin {
+    pub const DEFAULT_PAUSE_HOTKEY: KeyCode = KeyCode::F9;
+
+    /// Syncs the Bevy database into `rec`, once per frame during [`Last`].
+    pub fn new(rec: RecordingStream) -> Self {
+        Self {
+            rec,
+            sample_points: vec![RerunSamplePoint::default()],
+            worker_queue_capacity: 4,
+            log_stats: false,
+            opt_in: false,
+            entity_filters: Vec::new(),
+            pause_hotkey: None,
+            config_file: None,
+            path_strategy: None,
+            path_root: None,
+            view_coordinates: None,
+        }
+    }
+
+    /// Replaces the default sample point with `sample_points`.
+    ///
+    /// E.g. to snapshot the database both after [`FixedUpdate`] and during [`Last`]:
+    /// ```rust,ignore
+    /// revy::RerunPlugin::new(rec).with_sample_points([
+    ///     revy::RerunSamplePoint::new("sim_fixed_update", FixedPostUpdate),
+    ///     revy::RerunSamplePoint::new("sim_last", Last),
+    /// ])
+    /// ```
+    pub fn with_sample_points(
+        mut self,
+        sample_points: impl IntoIterator<Item = RerunSamplePoint>,
+    ) -> Self {
+        self.sample_points = sample_points.into_iter().collect();
+        self
+    }
+
+    /// How many snapshots can be waiting on the background logging worker at once.
+    ///
+    /// See [`RerunQueueFullPolicy`] for what happens when the worker cannot keep up.
+    pub fn with_worker_queue_capacity(mut self, capacity: usize) -> Self {
+        self.worker_queue_capacity = capacity;
+        self
+    }
+
+    /// Logs [`RerunSyncStats`] as scalars under `revy/stats`, every snapshot.
+    pub fn with_logged_stats(mut self) -> Self {
+        self.log_stats = true;
+        self
+    }
+
+    /// Only syncs the entities that have a [`RerunTrack`] component.
+    ///
+    /// [`RerunIgnore`] always takes precedence, whether in opt-in mode or not.
+    pub fn with_opt_in_tracking(mut self) -> Self {
+        self.opt_in = true;
+        self
+    }
+
+    /// Only syncs the entities that match `F`, e.g. `With<Player>`.
+    ///
+    /// Can be called several times, in which case entities must match all the filters.
+    /// Change detection filters (e.g. `Changed<T>`) are not supported.
+    pub fn with_entity_filter<F: QueryFilter + 'static>(mut self) -> Self {
+        self.entity_filters.push(new_entity_filter::<F>());
+        self
+    }
+
+    /// Pauses and resumes the sync whenever `hotkey` is pressed, see [`RerunControl`].
+    ///
+    /// See [`Self::DEFAULT_PAUSE_HOTKEY`] for a sensible default.
+    pub fn with_pause_hotkey(mut self, hotkey: KeyCode) -> Self {
+        self.pause_hotkey = Some(hotkey);
+        self
+    }
+
+    /// Loads a [`RevyConfig`] from `path` (e.g. `revy.ron`) through the [`AssetServer`], which must
+    /// be added before this plugin.
+    ///
+    /// The file is applied on top of the code configuration as soon as it is loaded, and again
+    /// every time it changes if Bevy's `file_watcher` feature is enabled. Each time, a full
+    /// snapshot gets re-logged.
+    pub fn with_config_file(mut self, path: impl Into<String>) -> Self {
+        self.config_file = Some(path.into());
+        self
+    }
+
+    /// Decides where entities end up in the recording, see [`RerunEntityPathStrategy`].
+    ///
+    /// Defaults to [`HierarchicalPathStrategy`].
+    pub fn with_entity_path_strategy(mut self, strategy: impl EntityPathStrategy) -> Self {
+        self.path_strategy = Some(RerunEntityPathStrategy::new(strategy));
+        self
+    }
+
+    /// Syncs all entities under `root` instead of `world`, see [`RerunEntityPathRoot`].
+    ///
+    /// E.g. to keep the content of two apps apart in the same recording. Within an app, entities
+    /// can get a root of their own with [`RerunRoot`], e.g. to keep 2D and 3D content apart.
+    pub fn with_root(mut self, root: impl Into<rerun::EntityPath>) -> Self {
+        self.path_root = Some(RerunEntityPathRoot(root.into()));
+        self
+    }
+
+    /// The coordinate system of the synced entities, logged at every root.
+    ///
+    /// Defaults to [`rerun::ViewCoordinates::RIGHT_HAND_Y_UP`], which is Bevy's.
+    /// E.g. `rerun::ViewCoordinates::RIGHT_HAND_Z_UP()` for content imported from CAD tools.
+    pub fn with_view_coordinates(mut self, view_coordinates: rerun::ViewCoordinates) -> Self {
+        self.view_coordinates = Some(view_coordinates);
+        self
+    }
 }
 
 impl Plugin for RerunPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(RerunSyncPlugin {
             rec: self.rec.clone(),
+            sample_points: self.sample_points.clone(),
+            worker_queue_capacity: self.worker_queue_capacity,
+            log_stats: self.log_stats,
+            opt_in: self.opt_in,
+            entity_filters: self.entity_filters.clone(),
+            pause_hotkey: self.pause_hotkey,
+            config_file: self.config_file.clone(),
+            path_strategy: self.path_strategy.clone(),
+            path_root: self.path_root.clone(),
+            view_coordinates: self.view_coordinates.clone(),
         });
     }
 }
 
 // ---
 
+mod asset_dependencies;
+mod batching;
+mod budget;
+mod component_rules;
+mod config;
+mod control;
 mod conversions;
 mod default_loggers;
 mod entity_path;
+mod filter;
+mod hashing;
+mod log_context;
+mod rate;
 mod rerun_logger;
+mod sample_point;
+mod stats;
 mod sync;
+mod worker;
 
-pub use self::conversions::ToRerun;
+pub use self::asset_dependencies::RerunAssetDependency;
+pub use self::batching::RerunSyncBatching;
+pub use self::budget::RerunSyncBudget;
+pub use self::component_rules::{RerunComponentRule, RerunComponentRules};
+pub use self::config::{
+    RevyConfig, RevyConfigEntityFilter, RevyConfigPathStrategy, RevyConfigSyncRates,
+};
+pub use self::control::{RerunControl, RerunControlEvent};
+pub use self::conversions::{ReflectToRerun, RerunBatches, ToRerun};
 pub use self::default_loggers::DefaultRerunComponentLoggers;
-pub use self::entity_path::{ancestors_from_world, compute_entity_path};
+pub use self::entity_path::{
+    ComponentPathStrategy, EntityPathStrategy, FlatPathStrategy, HierarchicalPathStrategy,
+    NamePathStrategy, RerunEntityPathRoot, RerunEntityPathStrategy, RerunRoot, StablePathStrategy,
+    ancestors_from_world, compute_entity_path,
+};
+pub use self::filter::{RerunIgnore, RerunTrack};
+pub use self::log_context::{RerunLogBuilder, RerunLogContext};
+pub use self::rate::{RerunEntitySyncRate, RerunSyncRate, RerunSyncRates};
 pub use self::rerun_logger::{
-    RerunComponentLoggers, RerunLogger, RerunLoggerFn, get_component_logger,
+    ReflectRerunLog, RerunComponentLoggers, RerunLog, RerunLogger, RerunLoggerAppExt,
+    RerunLoggerFn, RerunTypedComponentLoggers, get_component_logger,
 };
+pub use self::sample_point::{RerunSamplePoint, RerunSyncSet};
+pub use self::stats::RerunSyncStats;
+
+pub use self::worker::RerunQueueFullPolicy;
+
+pub use komotool_revy_derive::RerunLog;
 
 pub(crate) use self::sync::RerunSyncPlugin;
 
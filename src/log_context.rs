@@ -0,0 +1,222 @@
+use bevy::{
+    ecs::{component::ComponentInfo, entity::EntityHashMap},
+    platform::collections::HashMap,
+    prelude::*,
+};
+use rerun::AsComponents as _;
+
+use crate::{budget::CaptureTime, entity_path::peek_entity_path, sync::SyncedEntity};
+
+// ---
+
+/// Looks up the path of any entity, reusing the cached ones whenever possible.
+#[derive(Clone, Copy)]
+pub(crate) struct EntityPathLookup<'w> {
+    pub world: &'w World,
+    pub all_entities: &'w QueryState<(Entity, Option<&'w ChildOf>, Option<&'w Name>)>,
+
+    /// The entities that were synced so far, along with their cached paths.
+    pub synced_entities: &'w EntityHashMap<SyncedEntity>,
+
+    /// The paths of the entities that are being synced for the first time.
+    pub new_entity_paths: &'w EntityHashMap<rerun::EntityPath>,
+}
+
+impl EntityPathLookup<'_> {
+    pub fn entity_path(&self, entity_id: Entity) -> rerun::EntityPath {
+        self.synced_entities
+            .get(&entity_id)
+            .map(|synced_entity| &synced_entity.entity_path)
+            .or_else(|| self.new_entity_paths.get(&entity_id))
+            .cloned()
+            .unwrap_or_else(|| peek_entity_path(self.world, self.all_entities, entity_id))
+    }
+}
+
+/// Everything a [`crate::RerunLogger`] gets to know about the component it logs.
+pub struct RerunLogContext<'w> {
+    entity_paths: EntityPathLookup<'w>,
+    entity: EntityRef<'w>,
+    entity_path: rerun::EntityPath,
+    component: &'w ComponentInfo,
+    time: CaptureTime,
+}
+
+impl<'w> RerunLogContext<'w> {
+    pub(crate) fn new(
+        entity_paths: EntityPathLookup<'w>,
+        entity: EntityRef<'w>,
+        entity_path: rerun::EntityPath,
+        component: &'w ComponentInfo,
+        time: CaptureTime,
+    ) -> Self {
+        Self {
+            entity_paths,
+            entity,
+            entity_path,
+            component,
+            time,
+        }
+    }
+
+    #[inline]
+    pub fn world(&self) -> &'w World {
+        self.entity_paths.world
+    }
+
+    /// The entity that owns the component being logged.
+    #[inline]
+    pub fn entity(&self) -> EntityRef<'w> {
+        self.entity
+    }
+
+    /// The component being logged.
+    #[inline]
+    pub fn component(&self) -> &'w ComponentInfo {
+        self.component
+    }
+
+    /// The path of [`Self::entity`].
+    #[inline]
+    pub fn entity_path(&self) -> &rerun::EntityPath {
+        &self.entity_path
+    }
+
+    /// The path of any entity, e.g. to reference the parent of [`Self::entity`].
+    ///
+    /// Cached paths are reused whenever possible, see [`crate::RerunEntityPathStrategy`].
+    pub fn entity_path_of(&self, entity_id: Entity) -> rerun::EntityPath {
+        if entity_id == self.entity.id() {
+            return self.entity_path.clone();
+        }
+
+        self.entity_paths.entity_path(entity_id)
+    }
+
+    /// Looks up an asset, e.g. the material of [`Self::entity`]:
+    /// ```ignore
+    /// ctx.entity()
+    ///     .get::<MeshMaterial3d<StandardMaterial>>()
+    ///     .and_then(|handle| ctx.asset(handle))
+    /// ```
+    ///
+    /// Make sure to declare the dependency, see [`crate::RerunLogger::with_asset_dependency`].
+    #[inline]
+    pub fn asset<A: Asset>(&self, id: impl Into<AssetId<A>>) -> Option<&'w A> {
+        self.assets::<A>()?.get(id)
+    }
+
+    #[inline]
+    pub fn assets<A: Asset>(&self) -> Option<&'w Assets<A>> {
+        self.entity_paths.world.get_resource::<Assets<A>>()
+    }
+
+    /// The `sim_time` at which the component was captured, in seconds.
+    #[inline]
+    pub fn sim_time(&self) -> f64 {
+        self.time.sim_time
+    }
+
+    /// The [`bevy::diagnostic::FrameCount`] at which the component was captured.
+    #[inline]
+    pub fn frame(&self) -> u32 {
+        self.time.frame
+    }
+
+    #[inline]
+    pub fn type_registry(&self) -> &'w AppTypeRegistry {
+        self.entity_paths.world.resource::<AppTypeRegistry>()
+    }
+}
+
+// ---
+
+/// What a [`crate::RerunLogger`] logs, at the path of the entity and/or at any number of
+/// sub-paths, e.g.:
+/// ```ignore
+/// RerunLogBuilder::new()
+///     .with(&rerun::Transform3D::from_translation(translation))
+///     .with_at("aabb", &rerun::Boxes3D::from_half_sizes([half_size]))
+/// ```
+///
+/// Sub-paths end up at `<entity>/comps/<suffix>`, and can be generated at runtime, e.g. to log one
+/// sub-entity per item of an inventory:
+/// ```ignore
+/// inventory
+///     .slots
+///     .iter()
+///     .enumerate()
+///     .fold(RerunLogBuilder::new(), |builder, (index, slot)| {
+///         builder.with_at(format!("inventory/slot_{index}"), &slot.to_rerun())
+///     })
+/// ```
+///
+/// Whatever was logged at a sub-path that isn't produced anymore gets cleared automatically.
+#[derive(Debug, Default)]
+pub struct RerunLogBuilder {
+    batches: HashMap<Option<rerun::EntityPath>, Vec<rerun::SerializedComponentBatch>>,
+}
+
+impl RerunLogBuilder {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Logs `archetype` at the path of the entity.
+    #[inline]
+    pub fn with(self, archetype: &impl rerun::AsComponents) -> Self {
+        self.with_batches(archetype.as_serialized_batches())
+    }
+
+    /// Logs `batches` at the path of the entity.
+    #[inline]
+    pub fn with_batches(
+        self,
+        batches: impl IntoIterator<Item = rerun::SerializedComponentBatch>,
+    ) -> Self {
+        self.with_batches_at_suffix(None, batches)
+    }
+
+    /// Logs `archetype` at `<entity>/comps/<suffix>`.
+    #[inline]
+    pub fn with_at(
+        self,
+        suffix: impl Into<rerun::EntityPath>,
+        archetype: &impl rerun::AsComponents,
+    ) -> Self {
+        self.with_batches_at(suffix, archetype.as_serialized_batches())
+    }
+
+    /// Logs `batches` at `<entity>/comps/<suffix>`.
+    #[inline]
+    pub fn with_batches_at(
+        self,
+        suffix: impl Into<rerun::EntityPath>,
+        batches: impl IntoIterator<Item = rerun::SerializedComponentBatch>,
+    ) -> Self {
+        self.with_batches_at_suffix(Some(suffix.into()), batches)
+    }
+
+    fn with_batches_at_suffix(
+        mut self,
+        suffix: Option<rerun::EntityPath>,
+        batches: impl IntoIterator<Item = rerun::SerializedComponentBatch>,
+    ) -> Self {
+        self.batches.entry(suffix).or_default().extend(batches);
+        self
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.batches.values().all(Vec::is_empty)
+    }
+
+    /// The batches to log, grouped by entity path suffix.
+    #[inline]
+    pub(crate) fn into_batches(
+        self,
+    ) -> HashMap<Option<rerun::EntityPath>, Vec<rerun::SerializedComponentBatch>> {
+        self.batches
+    }
+}
@@ -0,0 +1,153 @@
+use bevy::{ecs::component::ComponentInfo, platform::collections::HashMap, prelude::*};
+use serde::Deserialize;
+
+use crate::budget::CaptureTime;
+
+// ---
+
+/// How often a component gets logged at most, see [`RerunSyncRates`].
+#[derive(Reflect, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub enum RerunSyncRate {
+    /// Every change gets logged.
+    #[default]
+    Unlimited,
+
+    /// At most once every that many frames.
+    EveryNFrames(u32),
+
+    /// At most that many times per second of `sim_time`.
+    MaxHz(f64),
+}
+
+impl RerunSyncRate {
+    /// Whether something last logged as of `last_logged` can be logged again as of `now`.
+    pub(crate) fn is_due(self, last_logged: Option<CaptureTime>, now: CaptureTime) -> bool {
+        let Some(last_logged) = last_logged else {
+            return true;
+        };
+
+        match self {
+            Self::Unlimited => true,
+            Self::EveryNFrames(frames) => now.frame.saturating_sub(last_logged.frame) >= frames,
+            Self::MaxHz(hz) => hz <= 0.0 || now.sim_time - last_logged.sim_time >= 1.0 / hz,
+        }
+    }
+}
+
+/// Limits how often components get logged, e.g. to log physics transforms at 10 Hz while keeping
+/// gameplay components exact:
+/// ```ignore
+/// RerunSyncRates::default()
+///     .with_global(RerunSyncRate::MaxHz(10.0))
+///     .with_component::<Health>(RerunSyncRate::Unlimited)
+/// ```
+///
+/// The rate of a given component of a given entity is, from highest to lowest precedence:
+/// 1. The rate set for that component type in [`Self::components`].
+/// 2. The rate set for the entity, see [`RerunEntitySyncRate`].
+/// 3. [`Self::global`].
+///
+/// Changes that come in too soon are not lost: the latest one is held back until the component is
+/// due again, then logged as of the time it was captured. Whatever is still held back gets logged
+/// right before the component is removed or the entity despawned, so the last value always makes
+/// it into the recording.
+///
+/// This cuts down on what gets logged, not on what gets captured: the loggers of rate-limited
+/// components still run every time they change.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct RerunSyncRates {
+    pub global: RerunSyncRate,
+    pub components: HashMap<rerun::ComponentName, RerunSyncRate>,
+}
+
+impl RerunSyncRates {
+    pub fn with_global(mut self, rate: RerunSyncRate) -> Self {
+        self.global = rate;
+        self
+    }
+
+    pub fn with_component<C: Component>(mut self, rate: RerunSyncRate) -> Self {
+        self.components
+            .insert(std::any::type_name::<C>().into(), rate);
+        self
+    }
+
+    pub(crate) fn rate_for(
+        &self,
+        entity: EntityRef<'_>,
+        component: &ComponentInfo,
+    ) -> RerunSyncRate {
+        if !self.components.is_empty() {
+            let component_name = rerun::ComponentName::from(component.name());
+            if let Some(rate) = self.components.get(&component_name) {
+                return *rate;
+            }
+        }
+
+        if let Some(RerunEntitySyncRate(rate)) = entity.get::<RerunEntitySyncRate>() {
+            return *rate;
+        }
+
+        self.global
+    }
+}
+
+/// Limits how often the components of this entity get logged, see [`RerunSyncRates`].
+#[derive(Component, Reflect, Debug, Default, Clone, Copy, PartialEq)]
+#[reflect(Component, Default)]
+pub struct RerunEntitySyncRate(pub RerunSyncRate);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(frame: u32, sim_time: f64) -> CaptureTime {
+        CaptureTime {
+            sim_time,
+            frame,
+            sample: None,
+        }
+    }
+
+    #[test]
+    fn never_logged_is_always_due() {
+        for rate in [
+            RerunSyncRate::Unlimited,
+            RerunSyncRate::EveryNFrames(100),
+            RerunSyncRate::MaxHz(0.001),
+        ] {
+            assert!(rate.is_due(None, at(0, 0.0)));
+        }
+    }
+
+    #[test]
+    fn unlimited_is_always_due() {
+        assert!(RerunSyncRate::Unlimited.is_due(Some(at(3, 1.0)), at(3, 1.0)));
+    }
+
+    #[test]
+    fn every_n_frames() {
+        let rate = RerunSyncRate::EveryNFrames(3);
+        assert!(!rate.is_due(Some(at(10, 0.0)), at(10, 0.0)));
+        assert!(!rate.is_due(Some(at(10, 0.0)), at(12, 0.0)));
+        assert!(rate.is_due(Some(at(10, 0.0)), at(13, 0.0)));
+        assert!(rate.is_due(Some(at(10, 0.0)), at(100, 0.0)));
+
+        // Frames going backwards, e.g. after a restart, never underflow.
+        assert!(!rate.is_due(Some(at(10, 0.0)), at(2, 0.0)));
+
+        assert!(RerunSyncRate::EveryNFrames(0).is_due(Some(at(10, 0.0)), at(10, 0.0)));
+    }
+
+    #[test]
+    fn max_hz() {
+        let rate = RerunSyncRate::MaxHz(10.0);
+        assert!(!rate.is_due(Some(at(0, 1.0)), at(0, 1.05)));
+        assert!(rate.is_due(Some(at(0, 1.0)), at(0, 1.1)));
+        assert!(rate.is_due(Some(at(0, 1.0)), at(0, 2.0)));
+
+        // Non-positive rates don't limit anything.
+        assert!(RerunSyncRate::MaxHz(0.0).is_due(Some(at(0, 1.0)), at(0, 1.0)));
+        assert!(RerunSyncRate::MaxHz(-1.0).is_due(Some(at(0, 1.0)), at(0, 1.0)));
+    }
+}
@@ -1,45 +1,26 @@
 use std::sync::Arc;
 
 use bevy::{
-    ecs::component::ComponentInfo,
+    ecs::component::{ComponentId, ComponentInfo},
     platform::collections::HashMap,
     prelude::*,
-    reflect::{ReflectFromPtr, serde::ReflectSerializer},
+    reflect::{FromType, ReflectFromPtr, TypeRegistry, serde::ReflectSerializer},
 };
 use rerun::ComponentBatch;
 
-use crate::DefaultRerunComponentLoggers;
+use crate::{DefaultRerunComponentLoggers, RerunAssetDependency, RerunLogBuilder, RerunLogContext};
 
 // ---
 
-// TODO(cmc): this should really work with component ids, although the API gotta uses names...
-// but that means doing things (such as defaults) lazily since components are themselves registered
-// lazily... and then it becomes a mess.
-
 /// The callback type to create a [`RerunLogger`].
-pub trait RerunLoggerFn:
-    Send
-    + Sync
-    + for<'w> Fn(
-        &'w World,
-        &'w QueryState<(Entity, Option<&'w ChildOf>, Option<&'w Name>)>,
-        EntityRef<'_>,
-        &'w ComponentInfo,
-    ) -> (Option<&'static str>, Vec<rerun::SerializedComponentBatch>)
-{
-}
-
-impl<F> RerunLoggerFn for F where
-    F: Send
-        + Sync
-        + for<'w> Fn(
-            &'w World,
-            &'w QueryState<(Entity, Option<&'w ChildOf>, Option<&'w Name>)>,
-            EntityRef<'_>,
-            &'w ComponentInfo,
-        ) -> (Option<&'static str>, Vec<rerun::SerializedComponentBatch>)
-{
-}
+///
+/// Returns whatever to log, at the path of the entity and/or at sub-paths, see [`RerunLogBuilder`].
+///
+/// Whatever a logger logged is cleared once it stops logging it, e.g. when the component gets
+/// removed. Sub-paths are expected to belong to a single component and are cleared as a whole.
+pub trait RerunLoggerFn: Send + Sync + Fn(&RerunLogContext<'_>) -> RerunLogBuilder {}
+
+impl<F> RerunLoggerFn for F where F: Send + Sync + Fn(&RerunLogContext<'_>) -> RerunLogBuilder {}
 
 #[derive(Clone)]
 pub enum BoxedOrStaticRerunLogger {
@@ -61,12 +42,23 @@ impl std::ops::Deref for BoxedOrStaticRerunLogger {
 
 /// An arbitrary callback to convert Bevy component data into Rerun component data.
 #[derive(Resource, Deref, Clone)]
-pub struct RerunLogger(BoxedOrStaticRerunLogger);
+pub struct RerunLogger {
+    #[deref]
+    logger: BoxedOrStaticRerunLogger,
+
+    /// The assets that the output of the logger depends on, if any.
+    asset_dependencies: Vec<RerunAssetDependency>,
+
+    /// See [`Self::with_low_priority`].
+    low_priority: bool,
+}
 
 impl std::fmt::Debug for RerunLogger {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_tuple("RerunLogger")
-            .field(&format!("{:p}", &self.0) as _)
+        f.debug_struct("RerunLogger")
+            .field("logger", &format!("{:p}", &self.logger) as _)
+            .field("asset_dependencies", &self.asset_dependencies)
+            .field("low_priority", &self.low_priority)
             .finish()
     }
 }
@@ -77,12 +69,66 @@ impl RerunLogger {
     where
         F: RerunLoggerFn + 'static,
     {
-        Self(BoxedOrStaticRerunLogger::Boxed(Arc::new(f) as _))
+        Self {
+            logger: BoxedOrStaticRerunLogger::Boxed(Arc::new(f) as _),
+            asset_dependencies: Vec::new(),
+            low_priority: false,
+        }
     }
 
     #[inline]
     pub const fn new_static(f: &'static dyn RerunLoggerFn) -> Self {
-        Self(BoxedOrStaticRerunLogger::Static(f))
+        Self {
+            logger: BoxedOrStaticRerunLogger::Static(f),
+            asset_dependencies: Vec::new(),
+            low_priority: false,
+        }
+    }
+
+    /// Runs the logger again for an entity whenever any of the assets of type `A` that it
+    /// references is added, modified or removed.
+    ///
+    /// `referenced_assets` returns the IDs of said assets for a given entity, e.g.:
+    /// ```ignore
+    /// logger.with_asset_dependency(|entity| {
+    ///     entity
+    ///         .get::<MeshMaterial3d<StandardMaterial>>()
+    ///         .map(|handle| handle.id())
+    /// })
+    /// ```
+    #[inline]
+    pub fn with_asset_dependency<A, I>(
+        mut self,
+        referenced_assets: impl Fn(EntityRef<'_>) -> I + Send + Sync + 'static,
+    ) -> Self
+    where
+        A: Asset,
+        I: IntoIterator<Item = AssetId<A>>,
+    {
+        self.asset_dependencies
+            .push(RerunAssetDependency::new(referenced_assets));
+        self
+    }
+
+    /// The assets that the output of this logger depends on.
+    #[inline]
+    pub fn asset_dependencies(&self) -> &[RerunAssetDependency] {
+        &self.asset_dependencies
+    }
+
+    /// Marks the output of this logger as something that can be dropped when the background
+    /// logging worker cannot keep up.
+    ///
+    /// See [`crate::RerunQueueFullPolicy::DropLowPriority`].
+    #[inline]
+    pub const fn with_low_priority(mut self) -> Self {
+        self.low_priority = true;
+        self
+    }
+
+    #[inline]
+    pub fn is_low_priority(&self) -> bool {
+        self.low_priority
     }
 }
 
@@ -92,37 +138,252 @@ impl RerunLogger {
 ///
 /// E.g. log `"bevy_transform::components::transform::Transform"` as [`rerun::Transform3D`].
 ///
+/// Prefer registering loggers by type with [`RerunLoggerAppExt::register_rerun_logger`], which
+/// cannot silently go stale when a type gets renamed or moved. Loggers registered by type take
+/// precedence over those registered by name, see [`RerunTypedComponentLoggers`].
+///
 /// Use `None` to prevent the data from being logged entirely.
 ///
 /// Don't set anything if you want to let the default logger to take over.
 /// See [`crate::DefaultRerunComponentLoggers`] for more information.
 ///
 /// If no default logger exists, the data will be logged as a [`rerun::TextDocument`].
-#[derive(Resource, Deref, DerefMut, Clone)]
+#[derive(Resource, Deref, DerefMut, Default, Clone)]
 pub struct RerunComponentLoggers(pub HashMap<rerun::ComponentName, Option<RerunLogger>>);
 
 impl RerunComponentLoggers {
     pub fn new(it: impl IntoIterator<Item = (rerun::ComponentName, Option<RerunLogger>)>) -> Self {
         Self(it.into_iter().collect())
     }
+
+    /// The logger registered for `component`, if any.
+    ///
+    /// `Some(None)` means that the component was explicitly disabled.
+    pub fn get_for(&self, component: &ComponentInfo) -> Option<Option<&RerunLogger>> {
+        if self.is_empty() {
+            return None;
+        }
+
+        self.get(&rerun::ComponentName::from(component.name()))
+            .map(|logger| logger.as_ref())
+    }
+}
+
+/// The [`RerunLogger`]s registered by type, see [`RerunLoggerAppExt`].
+///
+/// These are kept apart from [`RerunComponentLoggers`], so that inserting the latter at any point
+/// never discards them.
+#[derive(Resource, Deref, DerefMut, Default, Clone)]
+pub struct RerunTypedComponentLoggers(pub HashMap<ComponentId, Option<RerunLogger>>);
+
+impl RerunTypedComponentLoggers {
+    /// The logger registered for `component`, if any.
+    ///
+    /// `Some(None)` means that the component was explicitly disabled.
+    #[inline]
+    pub fn get_for(&self, component: &ComponentInfo) -> Option<Option<&RerunLogger>> {
+        self.get(&component.id()).map(|logger| logger.as_ref())
+    }
+}
+
+/// The logger registered for `component` by type or by name, if any.
+///
+/// `Some(None)` means that the component was explicitly disabled.
+pub(crate) fn get_registered_logger<'a>(
+    component: &ComponentInfo,
+    typed_loggers: Option<&'a RerunTypedComponentLoggers>,
+    loggers: Option<&'a RerunComponentLoggers>,
+) -> Option<Option<&'a RerunLogger>> {
+    typed_loggers
+        .and_then(|typed_loggers| typed_loggers.get_for(component))
+        .or_else(|| loggers.and_then(|loggers| loggers.get_for(component)))
+}
+
+/// A component that knows how to log itself.
+///
+/// Usually derived, and picked up by the sync through [`ReflectRerunLog`], e.g.:
+/// ```ignore
+/// #[derive(Component, Reflect, RerunLog, Debug)]
+/// #[reflect(Component, RerunLog)]
+/// struct Player {
+///     #[rerun(scalar)]
+///     health: f32,
+///     #[rerun(arrow)]
+///     velocity: Vec3,
+///     #[rerun(text)]
+///     name: String,
+///     #[rerun(skip)]
+///     #[reflect(ignore)]
+///     controller: ControllerHandle,
+///     state: PlayerState,
+/// }
+///
+/// app.register_type::<Player>();
+/// ```
+///
+/// Leaving out `#[reflect(RerunLog)]` is a compile error, since the sync would never find the
+/// type. Types that don't implement [`Reflect`] opt out with `#[rerun(manual)]` and are
+/// registered manually instead, see [`RerunLoggerAppExt::register_rerun_log`].
+///
+/// Everything gets logged under `comps/<snake_case_type_name>`, according to the attribute of
+/// each field:
+/// * `#[rerun(scalar)]`: plotted, using `as f64`.
+/// * `#[rerun(points3d)]`: drawn as a point, from a clone of anything that converts into
+///   `[f32; 3]` (e.g. [`Vec3`]).
+/// * `#[rerun(arrow)]`: drawn as an arrow from the entity's origin, same as above.
+/// * `#[rerun(text)]`: logged as text, using its `Display` implementation.
+/// * `#[rerun(skip)]`: not logged at all.
+/// * No attribute: logged as text, using its `Debug` implementation.
+///
+/// All the fields of a given kind are logged together as a single archetype, labeled by field
+/// name, so that they don't overwrite each other.
+pub trait RerunLog: Component {
+    fn rerun_log(&self, ctx: &RerunLogContext<'_>) -> RerunLogBuilder;
+}
+
+/// Type data for [`RerunLog`], so that the sync can find the components that know how to log
+/// themselves through the [`AppTypeRegistry`], see [`RerunLog`].
+///
+/// The sync registers their loggers in [`RerunTypedComponentLoggers`] as they get registered as
+/// components, unless a logger was already registered for them, which then takes precedence.
+#[derive(Clone)]
+pub struct ReflectRerunLog {
+    logger: RerunLogger,
+}
+
+impl ReflectRerunLog {
+    #[inline]
+    pub fn logger(&self) -> &RerunLogger {
+        &self.logger
+    }
+}
+
+impl<C: RerunLog + Reflect> FromType<C> for ReflectRerunLog {
+    fn from_type() -> Self {
+        Self {
+            logger: typed_logger::<C>(C::rerun_log),
+        }
+    }
+}
+
+/// Registers the loggers of all the components that have [`ReflectRerunLog`], as they get
+/// registered.
+#[derive(Default)]
+pub(crate) struct ReflectedRerunLoggers {
+    /// How many components were registered the last time this ran.
+    num_components: usize,
+
+    /// How many types had [`ReflectRerunLog`] the last time this ran.
+    num_reflected: usize,
+}
+
+impl ReflectedRerunLoggers {
+    pub fn update(&mut self, world: &mut World) {
+        let Some(type_registry) = world.get_resource::<AppTypeRegistry>().cloned() else {
+            return;
+        };
+        let type_registry = type_registry.read();
+
+        // NOTE: The type registry doesn't have change detection, and types can be registered
+        // before as well as after their components.
+        let num_components = world.components().len();
+        let num_reflected = type_registry.iter_with_data::<ReflectRerunLog>().count();
+        if (num_components, num_reflected) == (self.num_components, self.num_reflected) {
+            return;
+        }
+        self.num_components = num_components;
+        self.num_reflected = num_reflected;
+
+        let _trace = info_span!("update_reflected_rerun_loggers").entered();
+
+        let reflected_loggers = type_registry
+            .iter_with_data::<ReflectRerunLog>()
+            .filter_map(|(registration, reflect_rerun_log)| {
+                let component_id = world.components().get_id(registration.type_id())?;
+                Some((component_id, reflect_rerun_log.logger().clone()))
+            })
+            .collect::<Vec<_>>();
+        drop(type_registry);
+
+        let mut typed_loggers = world.get_resource_or_init::<RerunTypedComponentLoggers>();
+        for (component_id, logger) in reflected_loggers {
+            if !typed_loggers.contains_key(&component_id) {
+                typed_loggers.insert(component_id, Some(logger));
+            }
+        }
+    }
+}
+
+/// Registers [`RerunLogger`]s by type rather than by name, see [`RerunTypedComponentLoggers`].
+pub trait RerunLoggerAppExt {
+    /// Logs every `C` with `f`, e.g.:
+    /// ```ignore
+    /// app.register_rerun_logger::<Health>(|health: &Health, _ctx| {
+    ///     RerunLogBuilder::new().with_at("health", &rerun::Scalars::single(health.0))
+    /// });
+    /// ```
+    ///
+    /// `C` gets registered right away if it isn't already, so that the logger can be keyed by its
+    /// [`ComponentId`].
+    fn register_rerun_logger<C: Component>(
+        &mut self,
+        f: impl Fn(&C, &RerunLogContext<'_>) -> RerunLogBuilder + Send + Sync + 'static,
+    ) -> &mut Self;
+
+    /// Logs every `C` with its [`RerunLog`] implementation, usually derived.
+    ///
+    /// Only required for types that aren't registered with [`ReflectRerunLog`].
+    fn register_rerun_log<C: RerunLog>(&mut self) -> &mut Self;
+
+    /// Same as [`Self::register_rerun_logger`], for an existing [`RerunLogger`], or `None` to
+    /// prevent `C` from being logged entirely.
+    fn set_rerun_logger<C: Component>(&mut self, logger: Option<RerunLogger>) -> &mut Self;
+}
+
+impl RerunLoggerAppExt for App {
+    fn register_rerun_logger<C: Component>(
+        &mut self,
+        f: impl Fn(&C, &RerunLogContext<'_>) -> RerunLogBuilder + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.set_rerun_logger::<C>(Some(typed_logger(f)))
+    }
+
+    fn register_rerun_log<C: RerunLog>(&mut self) -> &mut Self {
+        self.register_rerun_logger::<C>(C::rerun_log)
+    }
+
+    fn set_rerun_logger<C: Component>(&mut self, logger: Option<RerunLogger>) -> &mut Self {
+        let world = self.world_mut();
+        let component_id = world.register_component::<C>();
+        world
+            .get_resource_or_init::<RerunTypedComponentLoggers>()
+            .insert(component_id, logger);
+        self
+    }
+}
+
+/// A [`RerunLogger`] that runs `f` on the value of `C`.
+fn typed_logger<C: Component>(
+    f: impl Fn(&C, &RerunLogContext<'_>) -> RerunLogBuilder + Send + Sync + 'static,
+) -> RerunLogger {
+    RerunLogger::new(move |ctx: &RerunLogContext<'_>| {
+        ctx.entity()
+            .get::<C>()
+            .map_or_else(RerunLogBuilder::new, |value| f(value, ctx))
+    })
 }
 
 pub fn get_component_logger<'a>(
     component: &ComponentInfo,
+    typed_loggers: Option<&'a RerunTypedComponentLoggers>,
     loggers: Option<&'a RerunComponentLoggers>,
     default_loggers: &'a DefaultRerunComponentLoggers,
 ) -> Option<&'a RerunLogger> {
-    let component_name = rerun::ComponentName::from(component.name());
-
-    if let Some(logger) = loggers.and_then(|loggers| {
-        loggers
-            .get(&component_name)
-            .as_ref()
-            .map(|logger| logger.as_ref())
-    }) {
+    if let Some(logger) = get_registered_logger(component, typed_loggers, loggers) {
         return logger;
     }
 
+    let component_name = rerun::ComponentName::from(component.name());
     if let Some(logger) = default_loggers
         .get(&component_name)
         .as_ref()
@@ -131,59 +392,85 @@ pub fn get_component_logger<'a>(
         return logger;
     }
 
-    #[allow(clippy::unnecessary_wraps)]
-    fn log_ignored_component(
-        world: &World,
-        _all_entities: &QueryState<(Entity, Option<&ChildOf>, Option<&Name>)>,
-        entity: EntityRef<'_>,
-        component: &ComponentInfo,
-    ) -> (Option<&'static str>, Vec<rerun::SerializedComponentBatch>) {
-        let name = component.name();
-        let body = component_to_ron(world, entity, component)
-            .unwrap_or_else(|| "<missing reflection metadata>".into());
-        (
-            None,
-            rerun::components::Text(body.into())
-                .serialized()
-                .into_iter()
-                .map(|batch| {
-                    batch.with_descriptor_override(rerun::ComponentDescriptor::new(
-                        name.replace("::", "."),
-                    ))
-                })
-                .collect(),
-        )
-    }
-
-    static LOG_IGNORED_COMPONENT: RerunLogger = RerunLogger::new_static(&log_ignored_component);
-
     Some(&LOG_IGNORED_COMPONENT)
 }
 
+/// The generic reflection-based logger, used for all components that have no logger of their own.
+///
+/// The sync never actually runs it: it snapshots the reflected component instead, and leaves the
+/// expensive serialization to the background logging worker.
+static LOG_IGNORED_COMPONENT: RerunLogger =
+    RerunLogger::new_static(&log_ignored_component).with_low_priority();
+
+/// Is `logger` the generic reflection-based logger?
+pub(crate) fn is_reflection_logger(logger: &RerunLogger) -> bool {
+    std::ptr::eq(logger, &LOG_IGNORED_COMPONENT)
+}
+
+fn log_ignored_component(ctx: &RerunLogContext<'_>) -> RerunLogBuilder {
+    let body = component_to_ron(ctx.world(), ctx.entity(), ctx.component());
+    RerunLogBuilder::new().with_batches(reflected_component_batch(
+        reflected_component_descriptor(ctx.component()),
+        body,
+    ))
+}
+
+/// The descriptor that the generic reflection-based logger logs `component` with.
+pub(crate) fn reflected_component_descriptor(
+    component: &ComponentInfo,
+) -> rerun::ComponentDescriptor {
+    rerun::ComponentDescriptor::new(component.name().replace("::", "."))
+}
+
+/// The output of the generic reflection-based logger, given the RON representation of a
+/// component, if any.
+pub(crate) fn reflected_component_batch(
+    descriptor: rerun::ComponentDescriptor,
+    ron: Option<String>,
+) -> Option<rerun::SerializedComponentBatch> {
+    let body = ron.unwrap_or_else(|| "<missing reflection metadata>".into());
+    rerun::components::Text(body.into())
+        .serialized()
+        .map(|batch| batch.with_descriptor_override(descriptor))
+}
+
 // TODO(cmc): why does this seem to fail for recursive types though? or is it something else?
 fn component_to_ron(
     world: &World,
     entity: EntityRef<'_>,
     component: &ComponentInfo,
 ) -> Option<String> {
+    with_reflected_component(world, entity, component, reflected_to_ron)
+}
+
+/// Runs `f` on the reflected value of `component`, if its type is registered with
+/// [`ReflectFromPtr`].
+pub(crate) fn with_reflected_component<R>(
+    world: &World,
+    entity: EntityRef<'_>,
+    component: &ComponentInfo,
+    f: impl FnOnce(&dyn PartialReflect, &TypeRegistry) -> Option<R>,
+) -> Option<R> {
     let type_registry = world.resource::<AppTypeRegistry>();
     let type_registry = type_registry.read();
 
-    component
-        .type_id()
-        .and_then(|tid| type_registry.get(tid))
-        .and_then(|ty| ty.data::<ReflectFromPtr>())
-        .and_then(|reflect_from_ptr| {
-            #[allow(unsafe_code)]
-            let reflected = entity
-                .get_by_id(component.id())
-                // Safety: the type registry cannot be wrong, surely
-                .map(|ptr| unsafe { reflect_from_ptr.as_reflect(ptr) });
-
-            reflected.ok().and_then(|reflected| {
-                let serializer =
-                    ReflectSerializer::new(reflected.as_partial_reflect(), &type_registry);
-                ron::ser::to_string_pretty(&serializer, ron::ser::PrettyConfig::default()).ok()
-            })
-        })
+    let reflect_from_ptr = type_registry
+        .get(component.type_id()?)?
+        .data::<ReflectFromPtr>()?;
+    let ptr = entity.get_by_id(component.id()).ok()?;
+
+    #[allow(unsafe_code)]
+    // Safety: `reflect_from_ptr` was looked up using the `TypeId` of the component, and `ptr`
+    // points to that very component.
+    let reflected = unsafe { reflect_from_ptr.as_reflect(ptr) };
+
+    f(reflected.as_partial_reflect(), &type_registry)
+}
+
+pub(crate) fn reflected_to_ron(
+    reflected: &dyn PartialReflect,
+    type_registry: &TypeRegistry,
+) -> Option<String> {
+    let serializer = ReflectSerializer::new(reflected, type_registry);
+    ron::ser::to_string_pretty(&serializer, ron::ser::PrettyConfig::default()).ok()
 }
@@ -0,0 +1,50 @@
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, InternedSystemSet, ScheduleLabel},
+    prelude::*,
+};
+
+// ---
+
+/// The system set that all the sync systems belong to, whatever their [`RerunSamplePoint`].
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RerunSyncSet;
+
+/// A point in the frame at which the Bevy database gets snapshotted into the Rerun database.
+///
+/// By default, a single snapshot is taken during [`Last`].
+///
+/// When more than one sample point is in use, every snapshot is additionally tagged on a
+/// `sim_sample` sequence that orders all the snapshots ever taken, as well as on a sequence named
+/// after its sample point. This is what makes intra-frame state changes visible.
+#[derive(Debug, Clone)]
+pub struct RerunSamplePoint {
+    pub(crate) name: &'static str,
+    pub(crate) schedule: InternedScheduleLabel,
+    pub(crate) after: Vec<InternedSystemSet>,
+}
+
+impl Default for RerunSamplePoint {
+    fn default() -> Self {
+        Self::new("sim_last", Last)
+    }
+}
+
+impl RerunSamplePoint {
+    /// Snapshots the database at some point during `schedule`.
+    ///
+    /// `name` is the name of the timeline that the snapshots taken at this sample point get tagged
+    /// on, e.g. `"sim_fixed_update"`.
+    pub fn new(name: &'static str, schedule: impl ScheduleLabel) -> Self {
+        Self {
+            name,
+            schedule: schedule.intern(),
+            after: Vec::new(),
+        }
+    }
+
+    /// Snapshots the database only once all the systems in `set` have run.
+    pub fn after<M>(mut self, set: impl IntoSystemSet<M>) -> Self {
+        self.after.push(set.into_system_set().intern());
+        self
+    }
+}
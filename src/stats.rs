@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use rerun::external::{arrow::array::Array as _, re_log::ResultExt as _};
+
+use crate::worker::{LogData, PendingLog};
+
+// ---
+
+/// What the last snapshot cost, see [`crate::RerunSamplePoint`].
+///
+/// Use [`crate::RerunPlugin::with_logged_stats`] to also log these under `revy/stats`.
+#[derive(Resource, Debug, Default, Clone, PartialEq)]
+pub struct RerunSyncStats {
+    /// How many entities had at least one component to sync.
+    pub entities_visited: u64,
+
+    /// How many components were flagged as changed, including the deduplicated ones.
+    pub components_changed: u64,
+
+    /// How many of the changed components turned out to be identical to what was last logged,
+    /// according to their hashes.
+    pub components_deduplicated: u64,
+
+    /// How many of the changed components were held back by their rate limits, see
+    /// [`crate::RerunSyncRates`].
+    pub components_deferred: u64,
+
+    /// How many entities didn't fit in the [`crate::RerunSyncBudget`] and will be captured during
+    /// the next frames instead.
+    pub entities_carried_over: u64,
+
+    /// How many batches of component data were handed over to the logging worker.
+    pub batches_logged: u64,
+
+    /// How many bytes worth of component data were handed over to the logging worker.
+    ///
+    /// This doesn't account for the components logged by the generic reflection-based logger,
+    /// which only get serialized by the worker itself.
+    pub bytes_logged: u64,
+
+    /// How many clears were handed over to the logging worker.
+    pub clears_logged: u64,
+
+    pub set_recording_time: Duration,
+    pub sync_components: Duration,
+    pub clear_despawned_entities: Duration,
+}
+
+impl RerunSyncStats {
+    /// Accounts for `logs` being handed over to the logging worker.
+    pub(crate) fn count_logs(&mut self, logs: &[PendingLog]) {
+        for batch in logs.iter().flat_map(|log| &log.batches) {
+            if batch.is_clear() {
+                self.clears_logged += 1;
+                continue;
+            }
+
+            match &batch.data {
+                LogData::Serialized(batch) => {
+                    self.batches_logged += 1;
+                    self.bytes_logged += batch.array.get_array_memory_size() as u64;
+                }
+
+                LogData::Reflected { .. } => self.batches_logged += 1,
+            }
+        }
+    }
+
+    /// Logs all the stats as [`rerun::Scalars`], under `revy/stats`.
+    pub(crate) fn log(&self, rec: &rerun::RecordingStream) {
+        let Self {
+            entities_visited,
+            components_changed,
+            components_deduplicated,
+            components_deferred,
+            entities_carried_over,
+            batches_logged,
+            bytes_logged,
+            clears_logged,
+            set_recording_time,
+            sync_components,
+            clear_despawned_entities,
+        } = self;
+
+        let scalars = [
+            ("entities_visited", *entities_visited as f64),
+            ("components_changed", *components_changed as f64),
+            ("components_deduplicated", *components_deduplicated as f64),
+            ("components_deferred", *components_deferred as f64),
+            ("entities_carried_over", *entities_carried_over as f64),
+            ("batches_logged", *batches_logged as f64),
+            ("bytes_logged", *bytes_logged as f64),
+            ("clears_logged", *clears_logged as f64),
+            ("time/set_recording_time", set_recording_time.as_secs_f64()),
+            ("time/sync_components", sync_components.as_secs_f64()),
+            (
+                "time/clear_despawned_entities",
+                clear_despawned_entities.as_secs_f64(),
+            ),
+        ];
+
+        for (name, value) in scalars {
+            rec.log(format!("revy/stats/{name}"), &rerun::Scalars::single(value))
+                .ok_or_log_error();
+        }
+    }
+}
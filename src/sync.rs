@@ -1,21 +1,43 @@
-use std::hash::Hash;
+use std::{sync::Arc, time::Instant};
 
-use ahash::AHasher;
 use bevy::{
     diagnostic::FrameCount,
     ecs::{
-        component::{ComponentId, ComponentInfo},
-        entity::EntityHashMap,
+        component::{ComponentId, ComponentInfo, StorageType, Tick},
+        entity::{EntityHashMap, EntityHashSet},
         event::EventCursor,
+        removal_detection::RemovedComponentEntity,
     },
-    platform::collections::HashMap,
+    platform::collections::{HashMap, HashSet},
     prelude::*,
-    reflect::{ReflectFromPtr, serde::ReflectSerializer},
+    ptr::UnsafeCellDeref as _,
+    reflect::PartialReflect,
+    tasks::{ComputeTaskPool, ParallelSlice as _, TaskPool},
 };
-use rerun::external::re_log::ResultExt;
+use rerun::{AsComponents as _, Loggable as _, external::re_log::ResultExt};
 
 use crate::{
-    DefaultRerunComponentLoggers, RerunComponentLoggers, compute_entity_path, get_component_logger,
+    DefaultRerunComponentLoggers, RerunComponentLoggers, RerunComponentRules, RerunEntitySyncRate,
+    RerunIgnore, RerunQueueFullPolicy, RerunSamplePoint, RerunSyncBatching, RerunSyncBudget,
+    RerunSyncRate, RerunSyncRates, RerunSyncSet, RerunSyncStats, RerunTrack,
+    RerunTypedComponentLoggers,
+    asset_dependencies::AssetEventsReaders,
+    budget::{CaptureBudget, CaptureTime, LogQueue, SampleTime},
+    component_rules::DeniedComponents,
+    compute_entity_path,
+    config::{RevyConfig, RevyConfigLoader, RevyConfigPathStrategy, RevyConfigState},
+    control::{RerunControl, RerunControlEvent, toggle_pause_on_hotkey},
+    conversions::{ReflectedToRerun, reflected_to_rerun},
+    entity_path::{RerunEntityPathRoot, RerunEntityPathStrategy, RerunRoot},
+    filter::{ComponentNamesFilter, EntityFilters, NewEntityFilter},
+    get_component_logger,
+    hashing::hash_reflected,
+    log_context::{EntityPathLookup, RerunLogContext},
+    rerun_logger::{
+        ReflectedRerunLoggers, is_reflection_logger, reflected_component_descriptor,
+        with_reflected_component,
+    },
+    worker::{LogData, LogWorker, QueuedBatch, Snapshot},
 };
 
 // ---
@@ -25,59 +47,539 @@ struct RerunSyncState {
     /// Where to publish the data?
     pub rec: rerun::RecordingStream,
 
-    /// Keeps track of alive entities so we can clear those that get despawned.
-    pub entities: EntityHashMap<rerun::EntityPath>,
+    /// Keeps track of alive entities so we can clear those that get despawned, as well as
+    /// everything else we need to know about them in order to sync them.
+    ///
+    /// This is kept on the side rather than as components on the entities themselves, so that
+    /// syncing never modifies the Bevy database in any way.
+    pub entities: EntityHashMap<SyncedEntity>,
+
+    /// Keeps track of the asset events that loggers depend on.
+    ///
+    /// See [`crate::RerunLogger::with_asset_dependency`].
+    pub asset_events: AssetEventsReaders,
+
+    /// Keeps track of the components that got removed from entities that are still alive.
+    pub removed_components: RemovedComponentsReaders,
+
+    /// Everything that was captured during the current frame but not logged yet.
+    pub log_queue: LogQueue,
+
+    /// Serializes and sends whatever leaves the [`Self::log_queue`], in the background.
+    pub log_worker: LogWorker,
+
+    /// How many snapshots can be waiting on the [`Self::log_worker`], kept around for restarts.
+    pub worker_queue_capacity: usize,
+
+    /// Keeps track of the [`RerunControlEvent`]s.
+    pub control_events: EventCursor<RerunControlEvent>,
+
+    /// Whether the sync was paused as of the last sample point, see [`RerunControl`].
+    pub paused: bool,
+
+    /// Whether the next snapshot must be a full one, see [`collect_all_components`].
+    pub full_snapshot: bool,
+
+    /// The config file, if any, see [`RevyConfig`].
+    pub config: Option<RevyConfigState>,
+
+    /// The path strategy in use as of the last sample point, so that everything can be re-logged
+    /// under the new paths when it changes.
+    pub path_strategy: Option<RerunEntityPathStrategy>,
+
+    /// The path root in use as of the last sample point, for the same reasons as
+    /// [`Self::path_strategy`].
+    pub path_root: Option<RerunEntityPathRoot>,
+
+    /// Logged at every root, see [`crate::RerunPlugin::with_view_coordinates`].
+    pub view_coordinates: rerun::ViewCoordinates,
+
+    /// The roots whose [`Self::view_coordinates`] were logged: the path root, and those of the
+    /// [`RerunRoot`]s in use as of the last sample point.
+    pub roots: HashSet<rerun::EntityPath>,
+
+    /// The change tick at which the last snapshot was taken, at any sample point.
+    ///
+    /// Every sample point runs its own instance of the sync system, so we cannot rely on
+    /// [`World::last_change_tick`] to tell what changed since the last snapshot.
+    pub last_change_tick: Option<Tick>,
+
+    /// The name of every [`RerunSamplePoint`] and how many snapshots were taken there so far.
+    pub sample_points: Vec<(&'static str, i64)>,
+
+    /// How many snapshots were taken so far, at any sample point.
+    pub samples: i64,
+
+    /// Whether [`RerunSyncStats`] should be logged too.
+    pub log_stats: bool,
+
+    /// Decides which entities get synced at all.
+    pub entity_filters: EntityFilters,
+
+    /// The components that never get synced, see [`crate::RerunComponentRules`].
+    pub denied_components: DeniedComponents,
+
+    /// Registers the loggers of the components that know how to log themselves, see
+    /// [`crate::ReflectRerunLog`].
+    pub reflected_loggers: ReflectedRerunLoggers,
+
+    /// The latest values of the components that changed too soon to be logged right away, see
+    /// [`RerunSyncRates`].
+    pub deferred: DeferredComponents,
+
+    /// The changes that didn't fit in the [`RerunSyncBudget`], in the order they'll be captured in.
+    pub carried_over: Vec<CarriedOverChange>,
+
+    /// The components whose data was dropped by the [`RerunQueueFullPolicy`], to be logged again.
+    pub dropped_components: Vec<(Entity, ComponentId)>,
+}
+
+impl RerunSyncState {
+    /// Applies the config file, if it was (re)loaded since the last sample point.
+    fn apply_config(&mut self, world: &mut World) {
+        let Some(config_state) = &mut self.config else {
+            return;
+        };
+        let Some(config) = config_state.poll(world) else {
+            return;
+        };
+
+        let _trace = info_span!("apply_config").entered();
+
+        let RevyConfig {
+            component_rules,
+            sync_rates,
+            entity_filter,
+            path_strategy,
+            disabled_default_loggers,
+        } = config;
+
+        RevyConfigState::replace_resource(
+            world,
+            &mut config_state.code_component_rules,
+            component_rules.map(RerunComponentRules),
+        );
+
+        RevyConfigState::replace_resource(
+            world,
+            &mut config_state.code_sync_rates,
+            sync_rates.map(|rates| RerunSyncRates {
+                global: rates.global,
+                components: rates
+                    .components
+                    .into_iter()
+                    .map(|(component_name, rate)| (component_name.into(), rate))
+                    .collect(),
+            }),
+        );
+
+        self.entity_filters.config = entity_filter.map(ComponentNamesFilter::new);
+
+        RevyConfigState::replace_resource(
+            world,
+            &mut config_state.code_path_strategy,
+            path_strategy.map(RevyConfigPathStrategy::to_strategy),
+        );
+
+        let mut default_loggers = world.resource_mut::<DefaultRerunComponentLoggers>();
+        default_loggers.extend(config_state.disabled_default_loggers.drain());
+        for component_name in disabled_default_loggers {
+            let component_name = rerun::ComponentName::from(component_name);
+            if let Some(logger) = default_loggers.remove(&component_name) {
+                config_state
+                    .disabled_default_loggers
+                    .insert(component_name, logger);
+            }
+        }
+
+        // Any entity and any component might be affected.
+        self.full_snapshot = true;
+
+        info!(path = %config_state.path, "applied revy's config");
+    }
+
+    /// Applies whatever [`RerunControl`] asks for.
+    ///
+    /// Returns whether the sync is paused.
+    fn apply_control(&mut self, world: &mut World) -> bool {
+        let events = world.resource::<Events<RerunControlEvent>>();
+        let events = self
+            .control_events
+            .read(events)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut control = world.resource_mut::<RerunControl>();
+        for event in &events {
+            control.apply(event);
+        }
+        let paused = control.is_paused();
+        let restart = control.take_restart();
+
+        if let Some(rec) = restart {
+            self.restart(world, rec);
+        }
+
+        if self.paused && !paused {
+            self.full_snapshot = true;
+        }
+        self.paused = paused;
+
+        paused
+    }
+
+    /// Starts syncing into `rec` from scratch.
+    fn restart(&mut self, world: &World, rec: rerun::RecordingStream) {
+        let _trace = info_span!("restart_sync").entered();
+
+        // Whatever was captured for the previous recording still goes there, including the values
+        // that were held back by the sync rates.
+        for (&entity_id, synced_entity) in &mut self.entities {
+            log_all_deferred_components(
+                entity_id,
+                synced_entity,
+                &mut self.deferred,
+                &mut self.log_queue,
+            );
+        }
+
+        let batching = *world.resource::<RerunSyncBatching>();
+        let logs = self.log_queue.flush();
+        _ = self
+            .log_worker
+            .send(Snapshot { batching, logs }, RerunQueueFullPolicy::Block);
+
+        for root in &self.roots {
+            log_static_data(&rec, root, &self.view_coordinates);
+        }
+
+        // NOTE: Dropping the previous worker waits for it to be done with the previous recording.
+        self.log_worker = LogWorker::spawn(
+            rec.clone(),
+            world
+                .get_resource::<AppTypeRegistry>()
+                .cloned()
+                .unwrap_or_default(),
+            self.worker_queue_capacity,
+        );
+        self.rec = rec;
+
+        self.entities.clear();
+        self.deferred.clear();
+        self.carried_over.clear();
+        self.dropped_components.clear();
+        self.log_queue = LogQueue::default();
+        self.samples = 0;
+        for (_, samples) in &mut self.sample_points {
+            *samples = 0;
+        }
+        self.full_snapshot = true;
+    }
+
+    /// Accounts for a new snapshot being taken at the `index`-th sample point.
+    ///
+    /// Returns `None` if there's only one sample point, in which case there's no need for extra
+    /// timelines.
+    fn take_sample(&mut self, index: usize) -> Option<SampleTime> {
+        let samples = self.samples;
+        self.samples += 1;
+
+        let (timeline, sample_point_samples) = self.sample_points.get_mut(index)?;
+        let timeline = *timeline;
+        let sample_index = *sample_point_samples;
+        *sample_point_samples += 1;
+
+        (self.sample_points.len() > 1).then_some(SampleTime {
+            timeline,
+            index: sample_index,
+            global_index: samples,
+        })
+    }
+}
+
+/// Everything the sync keeps track of for a given entity.
+#[derive(Debug, Clone)]
+pub(crate) struct SyncedEntity {
+    /// The cached entity path, kept up-to-date as the hierarchy changes.
+    ///
+    /// See `update_entity_paths`.
+    pub entity_path: rerun::EntityPath,
+
+    /// Everything we know about the components of the entity that were synced at least once.
+    components: HashMap<ComponentId, SyncedComponent>,
+}
+
+/// Everything the sync keeps track of for a given component of a given entity.
+#[derive(Debug, Clone)]
+struct SyncedComponent {
+    /// Used to deduplicate changes to components that don't actually change anything.
+    //
+    // TODO(cmc): we desperately need to be able to filter noise in the timeline panel.
+    hash: u64,
+
+    /// What the logger of the component logged the last time it ran.
+    ///
+    /// Used to clear that data once it isn't produced anymore, e.g. because the component was
+    /// removed from the entity.
+    logged: LoggedBatches,
+
+    /// When the component was last logged, see [`RerunSyncRates`].
+    last_logged: Option<CaptureTime>,
+}
+
+/// The descriptors that a logger logged, grouped by entity path suffix (`comps/<suffix>`).
+///
+/// These are stored as empty batches so that they can be logged as-is in order to clear the data.
+type LoggedBatches = HashMap<Option<rerun::EntityPath>, Vec<rerun::SerializedComponentBatch>>;
+
+/// The latest value of a component that changed too soon to be logged right away, waiting for its
+/// turn.
+///
+/// See [`RerunSyncRates`].
+struct DeferredComponent {
+    /// When the value was captured, which is also when it will be logged.
+    time: CaptureTime,
+
+    rate: RerunSyncRate,
+
+    /// The hash of the value and the descriptors that its logger logged.
+    hash: u64,
+    logged: LoggedBatches,
+
+    /// The output of the logger, grouped by entity path suffix.
+    batches: HashMap<Option<rerun::EntityPath>, Vec<QueuedBatch>>,
+}
+
+type DeferredComponents = EntityHashMap<HashMap<ComponentId, DeferredComponent>>;
+
+/// The changes of an entity that didn't fit in the [`RerunSyncBudget`], carried over to the next
+/// frames.
+struct CarriedOverChange {
+    entity_id: Entity,
+
+    /// When the changes were detected, which is also when they will be logged, unless the entity
+    /// changes again in the meantime.
+    time: CaptureTime,
+
+    components: Vec<(ComponentId, ComponentChange)>,
+}
+
+/// Logs whatever only needs to be logged once per recording (and root).
+fn log_static_data(
+    rec: &rerun::RecordingStream,
+    root: &rerun::EntityPath,
+    view_coordinates: &rerun::ViewCoordinates,
+) {
+    rec.log_static(root.clone(), view_coordinates)
+        .ok_or_log_error();
+}
+
+/// Undoes [`log_static_data`], e.g. once a root isn't in use anymore.
+fn clear_static_data(
+    rec: &rerun::RecordingStream,
+    root: &rerun::EntityPath,
+    view_coordinates: &rerun::ViewCoordinates,
+) {
+    let batches = view_coordinates
+        .as_serialized_batches()
+        .iter()
+        .map(empty_batch)
+        .collect::<Vec<_>>();
+    rec.log_static(root.clone(), &batches).ok_or_log_error();
+}
+
+/// Logs the static data at the roots that just came into use, and clears it from those that
+/// aren't in use anymore, see [`RerunRoot`].
+fn update_roots(
+    world: &mut World,
+    rec: &rerun::RecordingStream,
+    view_coordinates: &rerun::ViewCoordinates,
+    roots: &mut HashSet<rerun::EntityPath>,
+) {
+    let mut current_roots = world
+        .query::<&RerunRoot>()
+        .iter(world)
+        .map(|root| root.0.clone())
+        .collect::<HashSet<_>>();
+    current_roots.insert(world.resource::<RerunEntityPathRoot>().0.clone());
+
+    for root in roots.difference(&current_roots) {
+        clear_static_data(rec, root, view_coordinates);
+    }
+    for root in current_roots.difference(roots) {
+        log_static_data(rec, root, view_coordinates);
+    }
+
+    *roots = current_roots;
 }
 
 /// A plugin to sync the state of the Bevy database and the Rerun database.
 pub struct RerunSyncPlugin {
     pub rec: rerun::RecordingStream,
+    pub sample_points: Vec<RerunSamplePoint>,
+    pub worker_queue_capacity: usize,
+    pub pause_hotkey: Option<KeyCode>,
+    pub config_file: Option<String>,
+    pub path_strategy: Option<RerunEntityPathStrategy>,
+    pub path_root: Option<RerunEntityPathRoot>,
+    pub view_coordinates: Option<rerun::ViewCoordinates>,
+    pub log_stats: bool,
+    pub opt_in: bool,
+    pub entity_filters: Vec<NewEntityFilter>,
 }
 
 impl Plugin for RerunSyncPlugin {
     fn build(&self, app: &mut App) {
-        self.rec
-            .log_static("world", &rerun::ViewCoordinates::RIGHT_HAND_Y_UP())
-            .ok_or_log_error();
+        let path_root = self.path_root.clone().unwrap_or_default();
+        let view_coordinates = self
+            .view_coordinates
+            .clone()
+            .unwrap_or_else(rerun::ViewCoordinates::RIGHT_HAND_Y_UP);
+        log_static_data(&self.rec, &path_root, &view_coordinates);
+        let roots = HashSet::from_iter([path_root.0.clone()]);
+
+        let entity_filters = EntityFilters {
+            opt_in: self.opt_in,
+            filters: self
+                .entity_filters
+                .iter()
+                .map(|new_entity_filter| new_entity_filter(app.world_mut()))
+                .collect(),
+            config: None,
+        };
 
         let state = RerunSyncState {
             rec: self.rec.clone(),
             entities: Default::default(),
+            asset_events: Default::default(),
+            removed_components: Default::default(),
+            log_queue: Default::default(),
+            log_worker: LogWorker::spawn(
+                self.rec.clone(),
+                app.world()
+                    .get_resource::<AppTypeRegistry>()
+                    .cloned()
+                    .unwrap_or_default(),
+                self.worker_queue_capacity,
+            ),
+            worker_queue_capacity: self.worker_queue_capacity,
+            control_events: Default::default(),
+            paused: false,
+            full_snapshot: false,
+            config: self.config_file.clone().map(RevyConfigState::new),
+            path_strategy: None,
+            // The root everything starts off under, in case the app replaces it early on.
+            path_root: Some(path_root.clone()),
+            view_coordinates,
+            roots,
+            last_change_tick: None,
+            sample_points: self
+                .sample_points
+                .iter()
+                .map(|sample_point| (sample_point.name, 0))
+                .collect(),
+            samples: 0,
+            log_stats: self.log_stats,
+            entity_filters,
+            denied_components: Default::default(),
+            reflected_loggers: Default::default(),
+            deferred: Default::default(),
+            carried_over: Default::default(),
+            dropped_components: Default::default(),
         };
 
         app.init_resource::<DefaultRerunComponentLoggers>()
-            .insert_resource(state)
-            .add_systems(Last, system_sync_entities);
+            .init_resource::<RerunComponentRules>()
+            .init_resource::<RerunSyncRates>()
+            .init_resource::<RerunSyncBudget>()
+            .init_resource::<RerunSyncBatching>()
+            .init_resource::<RerunQueueFullPolicy>()
+            .init_resource::<RerunSyncStats>()
+            .init_resource::<RerunControl>()
+            .insert_resource(self.path_strategy.clone().unwrap_or_default())
+            .insert_resource(path_root)
+            .add_event::<RerunControlEvent>()
+            .register_type::<RerunIgnore>()
+            .register_type::<RerunTrack>()
+            .register_type::<RerunEntitySyncRate>()
+            .insert_resource(state);
+
+        for (index, sample_point) in self.sample_points.iter().enumerate() {
+            let mut system =
+                (move |world: &mut World| system_sync_entities(world, index)).in_set(RerunSyncSet);
+            for &set in &sample_point.after {
+                system = system.after(set);
+            }
+
+            app.add_systems(sample_point.schedule, system);
+        }
+
+        if self.config_file.is_some() {
+            app.init_asset::<RevyConfig>()
+                .init_asset_loader::<RevyConfigLoader>();
+        }
+
+        if let Some(hotkey) = self.pause_hotkey {
+            app.add_systems(PreUpdate, toggle_pause_on_hotkey(hotkey));
+        }
     }
 }
 
 // ---
 
-// TODO(cmc): some multithreading parallel iterators for the whole sync wouldn't hurt.
-
-fn system_sync_entities(world: &mut World) {
+/// Takes a snapshot at the `sample_point`-th [`RerunSamplePoint`].
+fn system_sync_entities(world: &mut World, sample_point: usize) {
     let _trace = info_span!("sync_entities").entered();
 
-    let state = world.resource::<RerunSyncState>();
-    let rec = state.rec.clone();
+    world.resource_scope(|world, mut state: Mut<'_, RerunSyncState>| {
+        let state = &mut *state;
 
-    // TODO(cmc): we should be subscribing to hierarchy event in order to clear old entity paths as
-    // their hierarchy changes (and thus their path).
+        state.apply_config(world);
+        if state.apply_control(world) {
+            return;
+        }
 
-    let mut previous_entities = state.entities.clone();
-    let mut current_entities = EntityHashMap::<rerun::EntityPath>::default();
-    {
-        set_recording_time(world, &rec);
-        sync_components(world, &mut current_entities, &mut previous_entities, &rec);
-        clear_despawned_entities(previous_entities, &rec);
-    }
+        let mut stats = RerunSyncStats::default();
+
+        let now = Instant::now();
+        let sample = state.take_sample(sample_point);
+        set_recording_time(world, &state.rec, &mut state.log_queue, sample);
+        stats.set_recording_time = now.elapsed();
+
+        let now = Instant::now();
+        sync_components(world, state, &mut stats);
+        stats.sync_components = now.elapsed();
+
+        let now = Instant::now();
+        clear_despawned_entities(
+            world,
+            &mut state.entities,
+            &mut state.deferred,
+            &mut state.log_queue,
+        );
+        stats.clear_despawned_entities = now.elapsed();
 
-    let mut state = world.resource_mut::<RerunSyncState>();
-    state.entities = current_entities;
+        let batching = *world.resource::<RerunSyncBatching>();
+        let policy = *world.resource::<RerunQueueFullPolicy>();
+        let logs = state.log_queue.flush();
+        stats.count_logs(&logs);
+        let dropped_components = state.log_worker.send(Snapshot { batching, logs }, policy);
+        state.dropped_components.extend(dropped_components);
+
+        if state.log_stats {
+            stats.log(&state.rec);
+        }
+        *world.resource_mut::<RerunSyncStats>() = stats;
+    });
 }
 
 /// Synchronize Bevy's clock with the recording's clock.
-fn set_recording_time(world: &World, rec: &rerun::RecordingStream) {
+fn set_recording_time(
+    world: &World,
+    rec: &rerun::RecordingStream,
+    log_queue: &mut LogQueue,
+    sample: Option<SampleTime>,
+) {
     let _trace = info_span!("set_recording_time").entered();
 
     let time = world.resource::<Time>();
@@ -87,187 +589,1202 @@ fn set_recording_time(world: &World, rec: &rerun::RecordingStream) {
     let frame = tick.0;
 
     rec.set_duration_secs("sim_time", elapsed);
+    log_queue.set_capture_time(CaptureTime {
+        sim_time: elapsed,
+        frame,
+        sample,
+    });
     // TODO(cmc): i'll log it once i can tell the blueprint to default to `sim_time`.
     // rec.set_time_sequence("sim_frame", frame);
-    _ = frame;
-}
-
-// TODO(cmc): implement proper subscription model for asset dependencies
-const DEPENDS_ON_IMAGES: &[&str] = &[
-    "bevy_render::mesh::components::Mesh3d",
-    "bevy_sprite::sprite::Sprite",
-];
-const DEPENDS_ON_MESHES: &[&str] = &[
-    "bevy_render::mesh::components::Mesh3d", //
-];
-const DEPENDS_ON_STDMATS: &[&str] = &[
-    "bevy_render::mesh::components::Mesh3d", //
-    "bevy_render::primitives::Aabb",         //
-];
-const DEPENDS_ON_COLMATS: &[&str] = &[
-    "bevy_render::mesh::components::Mesh3d", //
-    "bevy_render::primitives::Aabb",         //
-];
+}
+
+/// How many entities every thread of the compute task pool gets to capture at once, when there is a
+/// [`RerunSyncBudget`] to keep track of.
+const ENTITIES_PER_THREAD_PER_WAVE: usize = 32;
 
 /// Synchronize the Bevy and Rerun database by logging all components appropriately.
-//
-// TODO(cmc): obviously, iterating the world (literally, btw) is not a viable strategy.
-fn sync_components(
-    world: &mut World,
-    current_entities: &mut EntityHashMap<rerun::EntityPath>,
-    previous_entities: &mut EntityHashMap<rerun::EntityPath>,
-    rec: &rerun::RecordingStream,
-) {
-    let now = std::time::Instant::now();
+///
+/// Only the entities that had at least one of their components added or changed since the last
+/// sync are visited, see [`collect_changed_components`], unless a full snapshot was requested.
+fn sync_components(world: &mut World, state: &mut RerunSyncState, stats: &mut RerunSyncStats) {
+    let RerunSyncState {
+        rec,
+        entities,
+        asset_events,
+        removed_components,
+        log_queue,
+        log_worker: _,
+        worker_queue_capacity: _,
+        control_events: _,
+        paused: _,
+        full_snapshot,
+        config: _,
+        path_strategy,
+        path_root,
+        view_coordinates,
+        roots,
+        last_change_tick,
+        sample_points: _,
+        samples: _,
+        log_stats: _,
+        entity_filters,
+        denied_components,
+        reflected_loggers,
+        deferred,
+        carried_over,
+        dropped_components,
+    } = state;
+
+    let now = Instant::now();
 
     let _trace = info_span!("sync_components").entered();
 
     let mut all_entities = world.query::<(Entity, Option<&ChildOf>, Option<&Name>)>();
     all_entities.update_archetypes(world);
 
-    // TODO(cmc): do this the smart way
-    fn collect_events<A: Asset>(world: &mut World) -> Vec<AssetEvent<A>> {
-        let events = world.resource_mut::<Events<AssetEvent<A>>>();
-        let mut cursor = EventCursor::<AssetEvent<A>>::default();
-        cursor.read(&events).copied().collect()
+    let change_tick = world.read_change_tick();
+    let last_change_tick = last_change_tick
+        .replace(change_tick)
+        .unwrap_or_else(|| world.last_change_tick());
+
+    let mut changed_components = collect_changed_components(world, last_change_tick, change_tick);
+    let mut removed_components = removed_components.read(world);
+
+    // Whatever got dropped because the logging worker couldn't keep up gets logged again, as is.
+    for (entity_id, component_id) in dropped_components.drain(..) {
+        changed_components.insert(entity_id, component_id, ComponentChange::Forced);
+    }
+
+    // Changes carried over from the previous frames keep the time at which they were detected,
+    // unless their entity changed again since, see `RerunSyncBudget`.
+    let mut capture_order = Vec::with_capacity(carried_over.len());
+    let mut capture_times = EntityHashMap::default();
+    for CarriedOverChange {
+        entity_id,
+        time,
+        components,
+    } in carried_over.drain(..)
+    {
+        if !changed_components.contains_key(&entity_id) {
+            capture_times.insert(entity_id, time);
+        }
+        for (component_id, change) in components {
+            changed_components.insert(entity_id, component_id, change);
+        }
+        capture_order.push(entity_id);
+    }
+
+    let current_path_strategy = world.resource::<RerunEntityPathStrategy>().clone();
+    if path_strategy
+        .replace(current_path_strategy.clone())
+        .is_some_and(|path_strategy| !Arc::ptr_eq(&path_strategy.0, &current_path_strategy.0))
+    {
+        *full_snapshot = true;
+    }
+
+    let current_path_root = world.resource::<RerunEntityPathRoot>().clone();
+    if path_root
+        .replace(current_path_root.clone())
+        .is_some_and(|path_root| path_root != current_path_root)
+    {
+        *full_snapshot = true;
     }
-    let image_events = collect_events::<Image>(world);
-    let mesh_events = collect_events::<Mesh>(world);
-    let stdmat_events = collect_events::<StandardMaterial>(world);
-    let colmat_events = collect_events::<ColorMaterial>(world);
+
+    update_roots(world, rec, view_coordinates, roots);
+
+    let is_full_snapshot = std::mem::take(full_snapshot);
+    if is_full_snapshot {
+        collect_all_components(
+            world,
+            entities,
+            &mut changed_components,
+            &mut removed_components,
+        );
+    }
+
+    update_entity_paths(
+        world,
+        &all_entities,
+        entities,
+        &mut changed_components,
+        &removed_components,
+        is_full_snapshot,
+        deferred,
+        log_queue,
+    );
+
+    clear_removed_components(world, entities, deferred, &removed_components, log_queue);
+
+    reflected_loggers.update(world);
 
     // TODO(cmc): no good reason to clone this every time
+    let typed_loggers = world.get_resource::<RerunTypedComponentLoggers>().cloned();
     let loggers = world.get_resource::<RerunComponentLoggers>().cloned();
     let default_loggers = world.resource::<DefaultRerunComponentLoggers>().clone();
 
-    let mut deferred_hash_updates = Vec::new();
+    denied_components.update(world);
 
-    let mut entities = world.query::<Entity>();
-    for entity_id in entities.iter(world) {
-        // TODO(cmc): should cache this and deal with `HierarchyEvent` accordingly.
-        let entity_path = compute_entity_path(world, &all_entities, entity_id);
+    collect_asset_dependent_components(
+        world,
+        typed_loggers.as_ref(),
+        loggers.as_ref(),
+        &default_loggers,
+        denied_components,
+        asset_events,
+        &mut changed_components,
+    );
 
-        current_entities.insert(entity_id, entity_path.clone());
-        previous_entities.remove(&entity_id);
+    apply_entity_filters(
+        world,
+        entities,
+        deferred,
+        entity_filters,
+        &mut changed_components,
+        &removed_components,
+        log_queue,
+    );
 
-        let entity = world.entity(entity_id);
+    clear_denied_components(
+        entities,
+        deferred,
+        denied_components,
+        &changed_components,
+        log_queue,
+    );
+
+    // Whatever was carried over from the previous frames goes first, in the same order, followed
+    // by the new changes in a deterministic order.
+    let mut changed_components = changed_components.0;
+    let mut queue = capture_order
+        .into_iter()
+        .filter_map(|entity_id| changed_components.remove_entry(&entity_id))
+        .collect::<Vec<_>>();
+    let mut new_changes = changed_components.into_iter().collect::<Vec<_>>();
+    new_changes.sort_by_key(|(entity_id, _)| entity_id.index());
+    queue.extend(new_changes);
+
+    let capture_time = log_queue.capture_time();
+    let budget = *world.resource::<RerunSyncBudget>();
+    let task_pool = ComputeTaskPool::get_or_init(TaskPool::default);
+
+    // With a budget, entities get captured in waves, so that it can be checked in between.
+    let wave_size = match budget {
+        RerunSyncBudget::Unlimited => queue.len(),
+        RerunSyncBudget::Time(_) | RerunSyncBudget::Bytes(_) => {
+            task_pool.thread_num().max(1) * ENTITIES_PER_THREAD_PER_WAVE
+        }
+    }
+    .max(1);
+
+    // At least one wave always goes through, so that progress is made no matter the budget.
+    let mut capture_budget = CaptureBudget::new(budget);
+    let mut num_captured = 0;
+    while num_captured < queue.len() && (num_captured == 0 || !capture_budget.is_exhausted()) {
+        let wave = &queue[num_captured..(num_captured + wave_size).min(queue.len())];
+        num_captured += wave.len();
+
+        // Path strategies may keep track of the paths they hand out (see `NamePathStrategy`), so
+        // new entities get theirs serially and in a deterministic order, before the parallel pass.
+        let new_entity_paths = wave
+            .iter()
+            .filter(|(entity_id, _)| !entities.contains_key(entity_id))
+            .map(|&(entity_id, _)| {
+                (
+                    entity_id,
+                    compute_entity_path(world, &all_entities, entity_id),
+                )
+            })
+            .collect::<EntityHashMap<_>>();
+
+        // All the heavy lifting (hashes, loggers) only requires shared access to the world, so it
+        // gets spread across the compute task pool. Only the actual logging and the write-back of
+        // the bookkeeping happen serially afterwards.
+        let entity_syncs = {
+            let world: &World = world;
+            let rates = world.resource::<RerunSyncRates>();
+            let entity_paths = EntityPathLookup {
+                world,
+                all_entities: &all_entities,
+                synced_entities: entities,
+                new_entity_paths: &new_entity_paths,
+            };
+            let denied_components: &DeniedComponents = denied_components;
+            let capture_times = &capture_times;
+            wave.par_splat_map(task_pool, None, |_, chunk| {
+                chunk
+                    .iter()
+                    .map(|(entity_id, components)| {
+                        sync_entity(
+                            entity_paths,
+                            typed_loggers.as_ref(),
+                            loggers.as_ref(),
+                            &default_loggers,
+                            denied_components,
+                            rates,
+                            capture_times
+                                .get(entity_id)
+                                .copied()
+                                .unwrap_or(capture_time),
+                            *entity_id,
+                            components,
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+        };
 
-        let change_tick = world.read_change_tick();
-        let last_change_tick = world.last_change_tick();
-
-        let mut current_hashes = CurrentHashes::default();
-        let empty_hashes = CurrentHashes::default();
-        let last_hashes = world
-            .entity(entity_id)
-            .get::<CurrentHashes>()
-            .unwrap_or(&empty_hashes);
-
-        let mut all_batches: HashMap<
-            Option<&'static str>,
-            Vec<Vec<rerun::SerializedComponentBatch>>,
-        > = Default::default();
-        if let Ok(component_iter) = world.inspect_entity(entity_id) {
-            for component in component_iter {
-                let mut has_changed = entity
-                    .get_change_ticks_by_id(component.id())
-                    .is_some_and(|changes| changes.is_changed(last_change_tick, change_tick));
-
-                // TODO(cmc): implement proper subscription model for asset dependencies
-                has_changed |=
-                    !image_events.is_empty() && DEPENDS_ON_IMAGES.contains(&component.name());
-                has_changed |=
-                    !mesh_events.is_empty() && DEPENDS_ON_MESHES.contains(&component.name());
-                has_changed |=
-                    !stdmat_events.is_empty() && DEPENDS_ON_STDMATS.contains(&component.name());
-                has_changed |=
-                    !colmat_events.is_empty() && DEPENDS_ON_COLMATS.contains(&component.name());
-
-                if !has_changed {
-                    continue;
+        for EntitySync {
+            entity_id,
+            time,
+            entity_path,
+            components,
+            batches: all_batches,
+            stale,
+            deferred: entity_deferred,
+            superseded,
+            num_changed,
+            num_deduplicated,
+        } in entity_syncs.into_iter().flatten()
+        {
+            stats.entities_visited += 1;
+            stats.components_changed += num_changed;
+            stats.components_deduplicated += num_deduplicated;
+            stats.components_deferred += entity_deferred.len() as u64;
+
+            capture_budget.spend(all_batches.values().flatten());
+
+            if let Some(deferred) = deferred.get_mut(&entity_id) {
+                for component_id in superseded {
+                    deferred.remove(&component_id);
                 }
+            }
+            if !entity_deferred.is_empty() {
+                deferred
+                    .entry(entity_id)
+                    .or_default()
+                    .extend(entity_deferred);
+            }
 
-                {
-                    // NOTE: Default the hash to 0, that way `<missing reflection data>` will be mapped
-                    // to 0 and will be logged only once rather than every frame.
-                    let component_hash =
-                        component_to_hash(world, entity, component).unwrap_or(0u64);
-                    current_hashes.insert(component.id(), component_hash);
-                    if last_hashes.get(&component.id()) == Some(&component_hash) {
-                        continue;
-                    }
+            log_queue.with_capture_time(time, |log_queue| {
+                // Clear first, so that anything that gets logged again at the same place wins.
+                clear_stale_data(log_queue, &entity_path, &stale);
+
+                for (suffix, batches) in all_batches {
+                    log_queue.push(
+                        entity_path_with_suffix(&entity_path, suffix.as_ref()),
+                        batches,
+                    );
                 }
+            });
 
-                if let Some(logger) =
-                    get_component_logger(component, loggers.as_ref(), &default_loggers)
-                {
-                    let (suffix, batches) = logger(world, &all_entities, entity, component);
-                    all_batches.entry(suffix).or_default().push(batches);
+            entities.insert(
+                entity_id,
+                SyncedEntity {
+                    entity_path,
+                    components,
+                },
+            );
+        }
+    }
+
+    carried_over.extend(queue.drain(num_captured..).map(|(entity_id, components)| {
+        CarriedOverChange {
+            entity_id,
+            time: capture_times
+                .get(&entity_id)
+                .copied()
+                .unwrap_or(capture_time),
+            components,
+        }
+    }));
+    stats.entities_carried_over = carried_over.len() as u64;
+    if !carried_over.is_empty() {
+        trace!(
+            entities = carried_over.len(),
+            "sync budget exhausted, carrying changes over"
+        );
+    }
+
+    log_due_deferred_components(entities, deferred, log_queue, capture_time);
+
+    trace!(elapsed=?now.elapsed(), "component sync done");
+}
+
+/// Everything that [`sync_entity`] computed for a single entity, ready to be logged.
+struct EntitySync {
+    entity_id: Entity,
+
+    /// When the changes were detected, see [`CarriedOverChange`].
+    time: CaptureTime,
+
+    entity_path: rerun::EntityPath,
+
+    /// The state of all the components of the entity, including the ones that just changed.
+    components: HashMap<ComponentId, SyncedComponent>,
+
+    /// The output of the loggers, grouped by entity path suffix.
+    batches: HashMap<Option<rerun::EntityPath>, Vec<QueuedBatch>>,
+
+    /// Whatever the loggers logged last time but didn't log this time, and must be cleared.
+    stale: StaleData,
+
+    /// The components that changed too soon to be logged right away, see [`RerunSyncRates`].
+    deferred: HashMap<ComponentId, DeferredComponent>,
+
+    /// The components whose deferred values, if any, are outdated and must be dropped.
+    superseded: Vec<ComponentId>,
+
+    /// How many components were flagged as changed, see [`RerunSyncStats`].
+    num_changed: u64,
+
+    /// How many of those didn't need to be logged again, see [`RerunSyncStats`].
+    num_deduplicated: u64,
+}
+
+/// Computes the hashes and logger outputs for the changed components of a single entity.
+///
+/// This only requires shared access to the [`World`] and can therefore run on any thread.
+#[allow(clippy::too_many_arguments)]
+fn sync_entity(
+    entity_paths: EntityPathLookup<'_>,
+    typed_loggers: Option<&RerunTypedComponentLoggers>,
+    loggers: Option<&RerunComponentLoggers>,
+    default_loggers: &DefaultRerunComponentLoggers,
+    denied_components: &DeniedComponents,
+    rates: &RerunSyncRates,
+    capture_time: CaptureTime,
+    entity_id: Entity,
+    components: &[(ComponentId, ComponentChange)],
+) -> EntitySync {
+    let world = entity_paths.world;
+    let synced_entity = entity_paths.synced_entities.get(&entity_id);
+    let entity_path = entity_paths.entity_path(entity_id);
+
+    let entity = world.entity(entity_id);
+
+    let empty_components = HashMap::default();
+    let last_components =
+        synced_entity.map_or(&empty_components, |synced_entity| &synced_entity.components);
+    let mut current_components = last_components.clone();
+
+    let mut all_batches: HashMap<Option<rerun::EntityPath>, Vec<QueuedBatch>> = Default::default();
+    let mut stale = StaleData::default();
+    let mut deferred = HashMap::default();
+    let mut superseded = Vec::new();
+    let mut num_deduplicated = 0;
+    for &(component_id, change) in components {
+        let Some(component) = world.components().get_info(component_id) else {
+            continue;
+        };
+
+        // Changes that were carried over might not apply anymore, see [`RerunSyncBudget`].
+        if !entity.contains_id(component_id) {
+            continue;
+        }
+
+        // Denied components aren't even hashed, see `clear_denied_components`.
+        if denied_components.contains(component_id) {
+            continue;
+        }
+
+        // NOTE: Default the hash to 0, that way `<missing reflection data>` will be mapped
+        // to 0 and will be logged only once rather than every frame.
+        let component_hash = component_to_hash(world, entity, component).unwrap_or(0u64);
+        let last_component = last_components.get(&component_id);
+        if change == ComponentChange::Modified
+            && last_component.is_some_and(|last_component| last_component.hash == component_hash)
+        {
+            num_deduplicated += 1;
+            superseded.push(component_id);
+            continue;
+        }
+
+        let mut logged = LoggedBatches::default();
+        let mut batches: HashMap<Option<rerun::EntityPath>, Vec<QueuedBatch>> = Default::default();
+        if let Some(logger) =
+            get_component_logger(component, typed_loggers, loggers, default_loggers)
+        {
+            let to_rerun = is_reflection_logger(logger)
+                .then(|| component_to_rerun(world, entity, component))
+                .flatten();
+
+            if let Some(ReflectedToRerun::Whole(to_rerun_batches)) = to_rerun {
+                queue_serialized_batches(&mut logged, &mut batches, None, to_rerun_batches, false);
+            } else if is_reflection_logger(logger) {
+                // Serializing is expensive: take a snapshot and let the logging worker do it.
+                let descriptor = reflected_component_descriptor(component);
+                let batch = QueuedBatch {
+                    data: LogData::Reflected {
+                        descriptor: descriptor.clone(),
+                        value: component_to_reflected(world, entity, component),
+                    },
+                    low_priority: logger.is_low_priority(),
+                    component: None,
+                };
+                logged.insert(None, vec![empty_text_batch(descriptor)]);
+                batches.insert(None, vec![batch]);
+
+                // Every field gets its own sub-path, so that they neither overwrite each other nor
+                // whatever else gets logged at the path of the entity.
+                if let Some(ReflectedToRerun::Fields(fields)) = to_rerun {
+                    let component_part =
+                        rerun::EntityPathPart::new(component.name().replace("::", "."));
+                    for (field_path, field_batches) in fields {
+                        let suffix = std::iter::once(component_part.clone())
+                            .chain(field_path)
+                            .collect::<rerun::EntityPath>();
+                        queue_serialized_batches(
+                            &mut logged,
+                            &mut batches,
+                            Some(suffix),
+                            field_batches,
+                            logger.is_low_priority(),
+                        );
+                    }
+                }
+            } else {
+                let ctx = RerunLogContext::new(
+                    entity_paths,
+                    entity,
+                    entity_path.clone(),
+                    component,
+                    capture_time,
+                );
+                for (suffix, logger_batches) in logger(&ctx).into_batches() {
+                    queue_serialized_batches(
+                        &mut logged,
+                        &mut batches,
+                        suffix,
+                        logger_batches,
+                        logger.is_low_priority(),
+                    );
                 }
             }
-        } else {
-            warn!("Failed to inspect entity: {entity_id:?}");
+
+            logged.retain(|_, batches| !batches.is_empty());
+        }
+
+        // So that it can be logged again if it ever gets dropped, see `RerunQueueFullPolicy`.
+        for batch in batches.values_mut().flatten() {
+            batch.component = Some((entity_id, component_id));
+        }
+
+        // Forced changes (hierarchy changes, asset changes, newly tracked entities...) are never
+        // rate-limited.
+        let rate = match change {
+            ComponentChange::Modified => rates.rate_for(entity, component),
+            ComponentChange::Forced => RerunSyncRate::Unlimited,
+        };
+        let last_logged = last_component.and_then(|last_component| last_component.last_logged);
+        if !rate.is_due(last_logged, capture_time) {
+            deferred.insert(
+                component_id,
+                DeferredComponent {
+                    time: capture_time,
+                    rate,
+                    hash: component_hash,
+                    logged,
+                    batches,
+                },
+            );
+            continue;
+        }
+
+        superseded.push(component_id);
+
+        for (suffix, batches) in batches {
+            all_batches.entry(suffix).or_default().extend(batches);
+        }
+
+        if let Some(last_component) = last_component {
+            collect_stale_data(&last_component.logged, &logged, &mut stale);
+        }
+
+        current_components.insert(
+            component_id,
+            SyncedComponent {
+                hash: component_hash,
+                logged,
+                last_logged: Some(capture_time),
+            },
+        );
+    }
+
+    EntitySync {
+        entity_id,
+        time: capture_time,
+        entity_path,
+        components: current_components,
+        batches: all_batches,
+        stale,
+        deferred,
+        superseded,
+        num_changed: components.len() as u64,
+        num_deduplicated,
+    }
+}
+
+/// Whatever loggers logged previously but don't log anymore, see [`collect_stale_data`].
+#[derive(Debug, Default)]
+struct StaleData {
+    /// `comps/<suffix>` paths that aren't logged to anymore, and must be cleared as a whole.
+    suffixes: Vec<rerun::EntityPath>,
+
+    /// Descriptors that aren't logged anymore, and must be cleared one by one.
+    batches: LoggedBatches,
+}
+
+/// Collects everything that is part of `last` but not of `current` into `stale`.
+fn collect_stale_data(last: &LoggedBatches, current: &LoggedBatches, stale: &mut StaleData) {
+    for (suffix, last_batches) in last {
+        let current_batches = current.get(suffix);
+
+        if let (Some(suffix), None) = (suffix, current_batches) {
+            stale.suffixes.push(suffix.clone());
+            continue;
+        }
+
+        let stale_batches = last_batches
+            .iter()
+            .filter(|last_batch| {
+                !current_batches.is_some_and(|current_batches| {
+                    current_batches
+                        .iter()
+                        .any(|batch| batch.descriptor == last_batch.descriptor)
+                })
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if !stale_batches.is_empty() {
+            stale
+                .batches
+                .entry(suffix.clone())
+                .or_default()
+                .extend(stale_batches);
         }
+    }
+}
+
+/// Clears everything described by `stale`.
+///
+/// Stale descriptors are cleared by logging empty batches for them.
+fn clear_stale_data(log_queue: &mut LogQueue, entity_path: &rerun::EntityPath, stale: &StaleData) {
+    for suffix in &stale.suffixes {
+        log_queue.push(
+            entity_path_with_suffix(entity_path, Some(suffix)),
+            to_queued_batches(rerun::Clear::flat().as_serialized_batches()),
+        );
+    }
 
-        if !current_hashes.is_empty() {
-            deferred_hash_updates.push((entity_id, current_hashes));
+    for (suffix, batches) in &stale.batches {
+        log_queue.push(
+            entity_path_with_suffix(entity_path, suffix.as_ref()),
+            to_queued_batches(batches.clone()),
+        );
+    }
+}
+
+/// Logs every deferred component whose turn has come, see [`RerunSyncRates`].
+fn log_due_deferred_components(
+    entities: &mut EntityHashMap<SyncedEntity>,
+    deferred: &mut DeferredComponents,
+    log_queue: &mut LogQueue,
+    now: CaptureTime,
+) {
+    let _trace = info_span!("log_due_deferred_components").entered();
+
+    deferred.retain(|&entity_id, components| {
+        let Some(synced_entity) = entities.get_mut(&entity_id) else {
+            return false;
+        };
+
+        let due = components
+            .iter()
+            .filter(|(component_id, deferred)| {
+                let last_logged = synced_entity
+                    .components
+                    .get(*component_id)
+                    .and_then(|synced_component| synced_component.last_logged);
+                deferred.rate.is_due(last_logged, now)
+            })
+            .map(|(&component_id, _)| component_id)
+            .collect::<Vec<_>>();
+
+        for component_id in due {
+            if let Some(deferred) = components.remove(&component_id) {
+                log_deferred_component(log_queue, synced_entity, component_id, deferred);
+            }
         }
 
-        let mut current_components: HashMap<rerun::ComponentDescriptor, rerun::EntityPath> =
-            HashMap::default();
+        !components.is_empty()
+    });
+}
+
+/// Logs all the deferred components of an entity right away, e.g. because it is about to be
+/// cleared.
+fn log_all_deferred_components(
+    entity_id: Entity,
+    synced_entity: &mut SyncedEntity,
+    deferred: &mut DeferredComponents,
+    log_queue: &mut LogQueue,
+) {
+    for (component_id, deferred) in deferred.remove(&entity_id).into_iter().flatten() {
+        log_deferred_component(log_queue, synced_entity, component_id, deferred);
+    }
+}
+
+/// Logs a deferred component as of the time it was captured, clearing whatever it replaces.
+fn log_deferred_component(
+    log_queue: &mut LogQueue,
+    synced_entity: &mut SyncedEntity,
+    component_id: ComponentId,
+    deferred: DeferredComponent,
+) {
+    let DeferredComponent {
+        time,
+        rate: _,
+        hash,
+        logged,
+        batches,
+    } = deferred;
 
-        for (suffix, batches) in all_batches {
-            let entity_path: rerun::EntityPath = suffix.map_or_else(
-                || entity_path.clone(),
-                // NOTE(cmc): The extra `comps/` is crucial so that we can easily clear everything
-                // (we need a recursive clear but not really)
-                |suffix| entity_path.join(&"comps".into()).join(&suffix.into()),
+    let mut stale = StaleData::default();
+    if let Some(last_component) = synced_entity.components.get(&component_id) {
+        collect_stale_data(&last_component.logged, &logged, &mut stale);
+    }
+
+    let entity_path = &synced_entity.entity_path;
+    log_queue.with_capture_time(time, |log_queue| {
+        clear_stale_data(log_queue, entity_path, &stale);
+        for (suffix, batches) in batches {
+            log_queue.push(
+                entity_path_with_suffix(entity_path, suffix.as_ref()),
+                batches,
             );
+        }
+    });
+
+    synced_entity.components.insert(
+        component_id,
+        SyncedComponent {
+            hash,
+            logged,
+            last_logged: Some(time),
+        },
+    );
+}
+
+/// Returns an empty batch with the same descriptor and datatype as `batch`.
+fn empty_batch(batch: &rerun::SerializedComponentBatch) -> rerun::SerializedComponentBatch {
+    rerun::SerializedComponentBatch::new(
+        rerun::external::arrow::array::new_empty_array(batch.array.data_type()),
+        batch.descriptor.clone(),
+    )
+}
+
+/// Queues `serialized` for logging at `suffix`, and keeps track of it so it can be cleared later.
+fn queue_serialized_batches(
+    logged: &mut LoggedBatches,
+    batches: &mut HashMap<Option<rerun::EntityPath>, Vec<QueuedBatch>>,
+    suffix: Option<rerun::EntityPath>,
+    serialized: Vec<rerun::SerializedComponentBatch>,
+    low_priority: bool,
+) {
+    logged
+        .entry(suffix.clone())
+        .or_default()
+        .extend(serialized.iter().map(empty_batch));
+    batches
+        .entry(suffix)
+        .or_default()
+        .extend(serialized.into_iter().map(|batch| QueuedBatch {
+            data: LogData::Serialized(batch),
+            low_priority,
+            component: None,
+        }));
+}
+
+/// Returns an empty batch of [`rerun::components::Text`], as logged by the generic
+/// reflection-based logger.
+fn empty_text_batch(descriptor: rerun::ComponentDescriptor) -> rerun::SerializedComponentBatch {
+    rerun::SerializedComponentBatch::new(
+        rerun::external::arrow::array::new_empty_array(&rerun::components::Text::arrow_datatype()),
+        descriptor,
+    )
+}
+
+fn to_queued_batches(batches: Vec<rerun::SerializedComponentBatch>) -> Vec<QueuedBatch> {
+    batches.into_iter().map(Into::into).collect()
+}
+
+/// Where the output of a logger ends up, given the entity path suffix it asked for.
+fn entity_path_with_suffix(
+    entity_path: &rerun::EntityPath,
+    suffix: Option<&rerun::EntityPath>,
+) -> rerun::EntityPath {
+    suffix.map_or_else(
+        || entity_path.clone(),
+        // NOTE(cmc): The extra `comps/` is crucial so that we can easily clear everything
+        // (we need a recursive clear but not really)
+        |suffix| entity_path.join(&"comps".into()).join(suffix),
+    )
+}
+
+/// Flags every component of every entity as changed, so that the whole database gets logged
+/// again, e.g. after the sync was paused.
+///
+/// Whatever was removed in the meantime is flagged as such too, since the removal events might not
+/// be around anymore by then.
+fn collect_all_components(
+    world: &World,
+    entities: &EntityHashMap<SyncedEntity>,
+    changed_components: &mut ChangedComponents,
+    removed_components: &mut RemovedComponents,
+) {
+    let _trace = info_span!("collect_all_components").entered();
+
+    for archetype in world.archetypes().iter() {
+        for entity in archetype.entities() {
+            for component_id in archetype.components() {
+                changed_components.insert(entity.id(), component_id, ComponentChange::Forced);
+            }
+        }
+    }
+
+    for (&entity_id, synced_entity) in entities {
+        let Ok(entity) = world.get_entity(entity_id) else {
+            continue; // Despawned, see `clear_despawned_entities`.
+        };
 
-            for batches in &batches {
-                for batch in batches {
-                    current_components.insert(batch.descriptor.clone(), entity_path.clone());
+        let removed = synced_entity
+            .components
+            .keys()
+            .filter(|&&component_id| !entity.contains_id(component_id))
+            .copied()
+            .collect::<Vec<_>>();
+        if !removed.is_empty() {
+            removed_components
+                .entry(entity_id)
+                .or_default()
+                .extend(removed);
+        }
+    }
+}
+
+/// Returns every entity that had at least one of its components added or changed within
+/// `last_run..this_run`, along with the IDs of said components.
+///
+/// This works directly off of the change ticks stored in the tables and sparse sets, one archetype
+/// at a time, rather than looking up every component of every entity individually.
+fn collect_changed_components(world: &World, last_run: Tick, this_run: Tick) -> ChangedComponents {
+    let _trace = info_span!("collect_changed_components").entered();
+
+    let storages = world.storages();
+    let mut changed_components = ChangedComponents::default();
+
+    for archetype in world.archetypes().iter() {
+        if archetype.is_empty() {
+            continue;
+        }
+
+        let table = storages.tables.get(archetype.table_id());
+
+        for component_id in archetype.components() {
+            match archetype.get_storage_type(component_id) {
+                Some(StorageType::Table) => {
+                    let Some(changed_ticks) =
+                        table.and_then(|table| table.get_changed_ticks_slice_for(component_id))
+                    else {
+                        continue;
+                    };
+
+                    for entity in archetype.entities() {
+                        #[allow(unsafe_code)]
+                        // Safety: we're holding a shared reference to the world, nobody can be
+                        // writing to these ticks right now.
+                        let changed_tick =
+                            unsafe { changed_ticks[entity.table_row().as_usize()].read() };
+
+                        if changed_tick.is_newer_than(last_run, this_run) {
+                            changed_components.insert(
+                                entity.id(),
+                                component_id,
+                                ComponentChange::Modified,
+                            );
+                        }
+                    }
+                }
+
+                Some(StorageType::SparseSet) => {
+                    let Some(sparse_set) = storages.sparse_sets.get(component_id) else {
+                        continue;
+                    };
+
+                    for entity in archetype.entities() {
+                        if sparse_set
+                            .get_ticks(entity.id())
+                            .is_some_and(|ticks| ticks.is_changed(last_run, this_run))
+                        {
+                            changed_components.insert(
+                                entity.id(),
+                                component_id,
+                                ComponentChange::Modified,
+                            );
+                        }
+                    }
                 }
+
+                None => {}
             }
+        }
+    }
+
+    changed_components
+}
+
+/// Flags the components whose loggers depend on assets that were added, modified or removed since
+/// the last sync, for all the entities that actually reference said assets.
+///
+/// See [`crate::RerunLogger::with_asset_dependency`].
+fn collect_asset_dependent_components(
+    world: &World,
+    typed_loggers: Option<&RerunTypedComponentLoggers>,
+    loggers: Option<&RerunComponentLoggers>,
+    default_loggers: &DefaultRerunComponentLoggers,
+    denied_components: &DeniedComponents,
+    asset_events: &mut AssetEventsReaders,
+    changed_components: &mut ChangedComponents,
+) {
+    let _trace = info_span!("collect_asset_dependent_components").entered();
+
+    let dependent_components = world
+        .components()
+        .iter_registered()
+        .filter(|component| !denied_components.contains(component.id()))
+        .filter_map(|component| {
+            let logger = get_component_logger(component, typed_loggers, loggers, default_loggers)?;
+            (!logger.asset_dependencies().is_empty()).then_some((component.id(), logger))
+        })
+        .collect::<Vec<_>>();
+
+    let modified_assets = asset_events.read_modified_assets(
+        world,
+        dependent_components
+            .iter()
+            .flat_map(|(_, logger)| logger.asset_dependencies()),
+    );
+    if modified_assets.is_empty() {
+        return;
+    }
+
+    for archetype in world.archetypes().iter() {
+        for &(component_id, logger) in &dependent_components {
+            if !archetype.contains(component_id) {
+                continue;
+            }
+
+            for entity in archetype.entities() {
+                let is_affected = logger.asset_dependencies().iter().any(|dependency| {
+                    dependency.is_affected(world.entity(entity.id()), &modified_assets)
+                });
+
+                // NOTE: The component itself hasn't changed, so its hash won't have either.
+                if is_affected {
+                    changed_components.insert(entity.id(), component_id, ComponentChange::Forced);
+                }
+            }
+        }
+    }
+}
+
+/// Recomputes the entity paths of all entities whose path components (see
+/// [`crate::EntityPathStrategy::path_components`]) changed, as well as those of their descendants,
+/// and of their siblings if need be (see [`crate::EntityPathStrategy::depends_on_siblings`]).
+/// With `all`, the entity paths of all synced entities get recomputed instead.
+///
+/// Whenever the path of an entity did change, everything it logged at its old path gets cleared
+/// (see [`clear_entity`]) and all of its components are scheduled to be logged again under the new
+/// path.
+#[allow(clippy::too_many_arguments)]
+fn update_entity_paths<'w>(
+    world: &'w World,
+    all_entities: &'w QueryState<(Entity, Option<&'w ChildOf>, Option<&'w Name>)>,
+    entities: &mut EntityHashMap<SyncedEntity>,
+    changed_components: &mut ChangedComponents,
+    removed_components: &RemovedComponents,
+    all: bool,
+    deferred: &mut DeferredComponents,
+    log_queue: &mut LogQueue,
+) {
+    let _trace = info_span!("update_entity_paths").entered();
+
+    let path_strategy = world.resource::<RerunEntityPathStrategy>();
+    // Roots apply to whole hierarchies, no matter the strategy.
+    let mut path_components = path_strategy.path_components(world);
+    path_components.extend(world.component_id::<RerunRoot>());
+    let children_component = world.component_id::<Children>();
+
+    let mut dirty_entities = if all {
+        entities.keys().copied().collect::<Vec<_>>()
+    } else {
+        let changed = changed_components.iter().filter(|(_, components)| {
+            components
+                .iter()
+                .any(|(component_id, _)| path_components.contains(component_id))
+        });
+        let removed = removed_components.iter().filter(|(_, components)| {
+            components
+                .iter()
+                .any(|component_id| path_components.contains(component_id))
+        });
+        let mut dirty_entities = changed
+            .map(|(entity_id, _)| *entity_id)
+            .chain(removed.map(|(entity_id, _)| *entity_id))
+            .collect::<Vec<_>>();
+
+        // NOTE: On reparent, the siblings left behind get taken care of through the `Children` of
+        // their parent, see `StablePathStrategy::path_components`.
+        if path_strategy.depends_on_siblings() {
+            let siblings = dirty_entities
+                .iter()
+                .filter_map(|&entity_id| world.get::<ChildOf>(entity_id))
+                .filter_map(|child_of| world.get::<Children>(child_of.parent()))
+                .flat_map(|children| children.iter())
+                .collect::<Vec<_>>();
+            dirty_entities.extend(siblings);
+        }
+
+        dirty_entities
+    };
+
+    // Popped in `Entity` index order, see `NamePathStrategy`.
+    dirty_entities.sort_by_key(|entity_id| std::cmp::Reverse(entity_id.index()));
+
+    let mut visited = EntityHashSet::default();
+    while let Some(entity_id) = dirty_entities.pop() {
+        if !world.entities().contains(entity_id) || !visited.insert(entity_id) {
+            continue;
+        }
+
+        // The paths of all descendants depend on ours.
+        if let Some(children) = world.get::<Children>(entity_id) {
+            dirty_entities.extend(children.iter());
+        }
+
+        let Some(synced_entity) = entities.get_mut(&entity_id) else {
+            continue; // Never synced before, nothing to clean up.
+        };
+
+        let entity_path = compute_entity_path(world, all_entities, entity_id);
+        if synced_entity.entity_path == entity_path {
+            continue;
+        }
 
-            rec.log(entity_path, &batches).ok_or_log_error();
+        // The last values always make it into the recording, at the old path, before getting
+        // cleared.
+        log_all_deferred_components(entity_id, synced_entity, deferred, log_queue);
+
+        let old_entity_path = std::mem::replace(&mut synced_entity.entity_path, entity_path);
+
+        // NOTE: Not recursive: other entities might live under the old path, e.g. with
+        // `ComponentPathStrategy`. Descendants get visited and cleared on their own anyway.
+        clear_entity(log_queue, &old_entity_path);
+
+        let entity = world.entity(entity_id);
+        for component_id in entity.archetype().components() {
+            changed_components.insert(entity_id, component_id, ComponentChange::Forced);
+        }
+
+        // The parent keeps track of the paths of its children.
+        if let (Some(child_of), Some(children_component)) =
+            (entity.get::<ChildOf>(), children_component)
+        {
+            changed_components.insert(
+                child_of.parent(),
+                children_component,
+                ComponentChange::Forced,
+            );
         }
+    }
+}
+
+/// Clears whatever the loggers of the removed components logged, for all the entities that are
+/// still alive.
+///
+/// Entities that got despawned are taken care of by [`clear_despawned_entities`] instead.
+fn clear_removed_components(
+    world: &World,
+    entities: &mut EntityHashMap<SyncedEntity>,
+    deferred: &mut DeferredComponents,
+    removed_components: &RemovedComponents,
+    log_queue: &mut LogQueue,
+) {
+    let _trace = info_span!("clear_removed_components").entered();
+
+    for (entity_id, components) in removed_components.iter() {
+        let Some(synced_entity) = entities.get_mut(entity_id) else {
+            continue;
+        };
+        let Ok(entity) = world.get_entity(*entity_id) else {
+            continue;
+        };
+
+        let mut stale = StaleData::default();
+        for &component_id in components {
+            // Removed then inserted right back: it will simply be logged again.
+            if entity.contains_id(component_id) {
+                continue;
+            }
 
-        let empty_components = CurrentComponents::default();
-        let last_components = entity
-            .get::<CurrentComponents>()
-            .unwrap_or(&empty_components);
+            // The last value always makes it into the recording before getting cleared.
+            if let Some(deferred) = deferred
+                .get_mut(entity_id)
+                .and_then(|deferred| deferred.remove(&component_id))
+            {
+                log_deferred_component(log_queue, synced_entity, component_id, deferred);
+            }
 
-        for (component_desc, entity_path) in last_components.iter() {
-            if !current_components.contains_key(component_desc) {
-                rec.log(entity_path.clone(), &rerun::Clear::flat())
-                    .ok_or_log_error();
+            if let Some(synced_component) = synced_entity.components.remove(&component_id) {
+                collect_stale_data(
+                    &synced_component.logged,
+                    &LoggedBatches::default(),
+                    &mut stale,
+                );
             }
         }
+
+        clear_stale_data(log_queue, &synced_entity.entity_path, &stale);
     }
+}
+
+/// Clears whatever the loggers of the changed components that are now denied logged, see
+/// [`crate::RerunComponentRules`].
+fn clear_denied_components(
+    entities: &mut EntityHashMap<SyncedEntity>,
+    deferred: &mut DeferredComponents,
+    denied_components: &DeniedComponents,
+    changed_components: &ChangedComponents,
+    log_queue: &mut LogQueue,
+) {
+    let _trace = info_span!("clear_denied_components").entered();
+
+    for (entity_id, components) in changed_components.iter() {
+        let Some(synced_entity) = entities.get_mut(entity_id) else {
+            continue;
+        };
+
+        let mut stale = StaleData::default();
+        for &(component_id, _) in components {
+            if !denied_components.contains(component_id) {
+                continue;
+            }
+
+            // The last value always makes it into the recording before getting cleared.
+            if let Some(deferred) = deferred
+                .get_mut(entity_id)
+                .and_then(|deferred| deferred.remove(&component_id))
+            {
+                log_deferred_component(log_queue, synced_entity, component_id, deferred);
+            }
 
-    for (entity_id, hashes) in deferred_hash_updates {
-        world.entity_mut(entity_id).insert(hashes);
+            if let Some(synced_component) = synced_entity.components.remove(&component_id) {
+                collect_stale_data(
+                    &synced_component.logged,
+                    &LoggedBatches::default(),
+                    &mut stale,
+                );
+            }
+        }
+
+        clear_stale_data(log_queue, &synced_entity.entity_path, &stale);
     }
+}
 
-    trace!(elapsed=?now.elapsed(), "component sync done");
+/// Drops all the entities that shouldn't be synced, see [`EntityFilters`].
+///
+/// Only the entities that changed in some way can have changed their tracking status too:
+/// - The ones that stopped being tracked get cleared and forgotten, as if they were despawned.
+/// - The ones that started being tracked get all of their components synced.
+fn apply_entity_filters(
+    world: &World,
+    entities: &mut EntityHashMap<SyncedEntity>,
+    deferred: &mut DeferredComponents,
+    entity_filters: &mut EntityFilters,
+    changed_components: &mut ChangedComponents,
+    removed_components: &RemovedComponents,
+    log_queue: &mut LogQueue,
+) {
+    let _trace = info_span!("apply_entity_filters").entered();
+
+    entity_filters.update(world);
+
+    let candidates = changed_components
+        .keys()
+        .chain(removed_components.keys())
+        .copied()
+        .collect::<EntityHashSet>();
+
+    for entity_id in candidates {
+        let Ok(entity) = world.get_entity(entity_id) else {
+            changed_components.remove(&entity_id);
+            continue; // Despawned, see `clear_despawned_entities`.
+        };
+
+        if entity_filters.is_tracked(world, entity) {
+            if !entities.contains_key(&entity_id) {
+                for component_id in entity.archetype().components() {
+                    changed_components.insert(entity_id, component_id, ComponentChange::Forced);
+                }
+            }
+        } else {
+            changed_components.remove(&entity_id);
+            if let Some(mut synced_entity) = entities.remove(&entity_id) {
+                log_all_deferred_components(entity_id, &mut synced_entity, deferred, log_queue);
+                clear_entity(log_queue, &synced_entity.entity_path);
+                world
+                    .resource::<RerunEntityPathStrategy>()
+                    .forget(entity_id);
+            }
+        }
+    }
 }
 
 fn clear_despawned_entities(
-    previous_entities: EntityHashMap<rerun::EntityPath>,
-    rec: &rerun::RecordingStream,
+    world: &World,
+    entities: &mut EntityHashMap<SyncedEntity>,
+    deferred: &mut DeferredComponents,
+    log_queue: &mut LogQueue,
 ) {
     let _trace = info_span!("clear_despawned_entities").entered();
 
-    for (_entity, entity_path) in previous_entities {
-        rec.log(
-            entity_path.join(&"comps".into()),
-            &rerun::Clear::recursive(),
-        )
-        .ok_or_log_error();
+    let path_strategy = world.resource::<RerunEntityPathStrategy>();
 
-        rec.log(entity_path, &rerun::Clear::flat())
-            .ok_or_log_error();
-    }
+    entities.retain(|&entity_id, synced_entity| {
+        if world.entities().contains(entity_id) {
+            return true;
+        }
+
+        // The last values always make it into the recording before getting cleared.
+        log_all_deferred_components(entity_id, synced_entity, deferred, log_queue);
+        clear_entity(log_queue, &synced_entity.entity_path);
+        path_strategy.forget(entity_id);
+
+        false
+    });
+}
+
+/// Clears everything that was ever logged for an entity.
+fn clear_entity(log_queue: &mut LogQueue, entity_path: &rerun::EntityPath) {
+    log_queue.push(
+        entity_path.join(&"comps".into()),
+        to_queued_batches(rerun::Clear::recursive().as_serialized_batches()),
+    );
+
+    log_queue.push(
+        entity_path.clone(),
+        to_queued_batches(rerun::Clear::flat().as_serialized_batches()),
+    );
 }
 
 // ---
@@ -277,42 +1794,215 @@ fn component_to_hash(
     entity: EntityRef<'_>,
     component: &ComponentInfo,
 ) -> Option<u64> {
-    let type_registry = world.resource::<AppTypeRegistry>();
-    let type_registry = type_registry.read();
-
-    component
-        .type_id()
-        .and_then(|tid| type_registry.get(tid))
-        .and_then(|ty| ty.data::<ReflectFromPtr>())
-        .and_then(|reflect_from_ptr| {
-            #[allow(unsafe_code)]
-            let reflected = entity
-                .get_by_id(component.id())
-                // Safety: the type registry cannot be wrong, surely
-                .map(|ptr| unsafe { reflect_from_ptr.as_reflect(ptr) });
-
-            // TODO(cmc): `Reflect::reflect_hash` is basically never available so we go the long way
-            // instead... this is likely waaay too costly in practice :)
-            reflected.ok().and_then(|reflected| {
-                let serializer =
-                    ReflectSerializer::new(reflected.as_partial_reflect(), &type_registry);
-                let serialized = ron::ser::to_string(&serializer).ok()?;
-                let bytes = serialized.into_bytes();
-
-                use std::hash::Hasher;
-                let mut hasher = AHasher::default();
-                bytes.hash(&mut hasher);
-                Some(hasher.finish())
-            })
-        })
+    with_reflected_component(world, entity, component, hash_reflected)
+}
+
+/// Converts a component using [`crate::ReflectToRerun`], if it or any of its fields has it.
+fn component_to_rerun(
+    world: &World,
+    entity: EntityRef<'_>,
+    component: &ComponentInfo,
+) -> Option<ReflectedToRerun> {
+    with_reflected_component(world, entity, component, reflected_to_rerun)
+}
+
+/// Takes a snapshot of a component, to be serialized later on by the logging worker.
+fn component_to_reflected(
+    world: &World,
+    entity: EntityRef<'_>,
+    component: &ComponentInfo,
+) -> Option<Box<dyn PartialReflect>> {
+    with_reflected_component(world, entity, component, |reflected, _| {
+        // NOTE: Dynamic values cannot always be serialized, prefer actual clones.
+        Some(reflected.reflect_clone().map_or_else(
+            |_err| reflected.to_dynamic(),
+            |cloned| cloned.into_partial_reflect(),
+        ))
+    })
+}
+
+/// Why a component needs to be synced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ComponentChange {
+    /// The component was added or modified according to its change ticks.
+    ///
+    /// It will still be deduplicated using its hash, see [`SyncedComponent::hash`].
+    Modified,
+
+    /// The component must be logged no matter what, e.g. because its entity path changed.
+    Forced,
+}
+
+/// All the components that need to be synced, for every entity that has any.
+#[derive(Debug, Default, Deref, DerefMut)]
+struct ChangedComponents(EntityHashMap<Vec<(ComponentId, ComponentChange)>>);
+
+impl ChangedComponents {
+    /// Flags a component as changed, [`ComponentChange::Forced`] always takes precedence.
+    fn insert(&mut self, entity_id: Entity, component_id: ComponentId, change: ComponentChange) {
+        let components = self.0.entry(entity_id).or_default();
+        if let Some((_, existing)) = components.iter_mut().find(|(id, _)| *id == component_id) {
+            if change == ComponentChange::Forced {
+                *existing = change;
+            }
+        } else {
+            components.push((component_id, change));
+        }
+    }
 }
 
-/// Used to deduplicate changes to components that don't actually change anything.
-//
-// TODO(cmc): we desperately need to be able to filter noise in the timeline panel.
-#[derive(Component, Debug, Clone, Default, Deref, DerefMut)]
-struct CurrentHashes(HashMap<ComponentId, u64>);
+/// All the components that were removed since the last sync, for every entity that had any.
+type RemovedComponents = EntityHashMap<Vec<ComponentId>>;
+
+/// Keeps track of which removal events have already been read, for every component.
+///
+/// Unlike [`World::removed`], this doesn't miss the removals that happened after the sync ran
+/// during the previous update.
+#[derive(Default)]
+struct RemovedComponentsReaders(HashMap<ComponentId, EventCursor<RemovedComponentEntity>>);
 
-/// Keeps track of all components on an entity in order to `Clear` removed ones.
-#[derive(Component, Debug, Clone, Default, Deref, DerefMut)]
-struct CurrentComponents(HashMap<rerun::ComponentDescriptor, rerun::EntityPath>);
+impl RemovedComponentsReaders {
+    /// Reads all the removal events that were sent since the last call.
+    fn read(&mut self, world: &World) -> RemovedComponents {
+        let _trace = info_span!("read_removed_components").entered();
+
+        let mut removed_components = RemovedComponents::default();
+        for (&component_id, events) in world.removed_components().iter() {
+            let cursor = self.0.entry(component_id).or_default();
+            for event in cursor.read(events) {
+                let entity_id: Entity = event.clone().into();
+                removed_components
+                    .entry(entity_id)
+                    .or_default()
+                    .push(component_id);
+            }
+        }
+
+        removed_components
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::StablePathStrategy;
+
+    use super::*;
+
+    #[test]
+    fn renaming_an_entity_renumbers_its_siblings() {
+        let mut world = World::new();
+        world.insert_resource(RerunEntityPathStrategy::new(StablePathStrategy::default()));
+
+        let parent = world.spawn(Name::new("parent")).id();
+        let a = world.spawn((Name::new("x"), ChildOf(parent))).id();
+        let b = world.spawn((Name::new("y"), ChildOf(parent))).id();
+
+        let mut all_entities = world.query::<(Entity, Option<&ChildOf>, Option<&Name>)>();
+        let mut entities = [parent, a, b]
+            .into_iter()
+            .map(|entity_id| {
+                let synced_entity = SyncedEntity {
+                    entity_path: compute_entity_path(&world, &all_entities, entity_id),
+                    components: Default::default(),
+                };
+                (entity_id, synced_entity)
+            })
+            .collect::<EntityHashMap<_>>();
+
+        world.entity_mut(a).insert(Name::new("y"));
+        all_entities.update_archetypes(&world);
+
+        let mut changed_components = ChangedComponents::default();
+        changed_components.insert(
+            a,
+            world.component_id::<Name>().unwrap(),
+            ComponentChange::Modified,
+        );
+
+        update_entity_paths(
+            &world,
+            &all_entities,
+            &mut entities,
+            &mut changed_components,
+            &RemovedComponents::default(),
+            false,
+            &mut DeferredComponents::default(),
+            &mut LogQueue::default(),
+        );
+
+        let path = |parts: &[&str]| {
+            parts
+                .iter()
+                .map(|&part| rerun::EntityPathPart::new(part))
+                .collect::<rerun::EntityPath>()
+        };
+        assert_eq!(entities[&a].entity_path, path(&["world", "parent", "y"]));
+        assert_eq!(entities[&b].entity_path, path(&["world", "parent", "y[1]"]));
+
+        // Same as if the siblings had been named that way from the start.
+        for entity_id in [parent, a, b] {
+            assert_eq!(
+                entities[&entity_id].entity_path,
+                compute_entity_path(&world, &all_entities, entity_id)
+            );
+        }
+        assert!(changed_components.contains_key(&b));
+    }
+
+    #[test]
+    fn adding_a_root_moves_the_whole_hierarchy() {
+        let mut world = World::new();
+        world.insert_resource(RerunEntityPathStrategy::new(StablePathStrategy::default()));
+
+        let parent = world.spawn(Name::new("parent")).id();
+        let child = world.spawn((Name::new("child"), ChildOf(parent))).id();
+        let other = world.spawn(Name::new("other")).id();
+
+        let mut all_entities = world.query::<(Entity, Option<&ChildOf>, Option<&Name>)>();
+        let mut entities = [parent, child, other]
+            .into_iter()
+            .map(|entity_id| {
+                let synced_entity = SyncedEntity {
+                    entity_path: compute_entity_path(&world, &all_entities, entity_id),
+                    components: Default::default(),
+                };
+                (entity_id, synced_entity)
+            })
+            .collect::<EntityHashMap<_>>();
+
+        world.entity_mut(parent).insert(RerunRoot("world2d".into()));
+        all_entities.update_archetypes(&world);
+
+        let mut changed_components = ChangedComponents::default();
+        changed_components.insert(
+            parent,
+            world.component_id::<RerunRoot>().unwrap(),
+            ComponentChange::Modified,
+        );
+
+        update_entity_paths(
+            &world,
+            &all_entities,
+            &mut entities,
+            &mut changed_components,
+            &RemovedComponents::default(),
+            false,
+            &mut DeferredComponents::default(),
+            &mut LogQueue::default(),
+        );
+
+        let path = |parts: &[&str]| {
+            parts
+                .iter()
+                .map(|&part| rerun::EntityPathPart::new(part))
+                .collect::<rerun::EntityPath>()
+        };
+        assert_eq!(entities[&parent].entity_path, path(&["world2d", "parent"]));
+        assert_eq!(
+            entities[&child].entity_path,
+            path(&["world2d", "parent", "child"])
+        );
+        assert_eq!(entities[&other].entity_path, path(&["world", "other"]));
+        assert!(changed_components.contains_key(&child));
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    sync::mpsc::{Receiver, SyncSender, TrySendError},
+    thread::JoinHandle,
+};
+
+use bevy::{
+    ecs::component::ComponentId,
+    prelude::*,
+    reflect::{PartialReflect, TypeRegistry},
+};
+use rerun::external::arrow::array::Array as _;
+
+use crate::{
+    RerunSyncBatching,
+    batching::LogSink,
+    budget::CaptureTime,
+    rerun_logger::{reflected_component_batch, reflected_to_ron},
+};
+
+// ---
+
+/// What to do when the background logging worker cannot keep up with the sync, i.e. when its
+/// queue is full.
+///
+/// The capacity of the queue is set using [`crate::RerunPlugin::with_worker_queue_capacity`].
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RerunQueueFullPolicy {
+    /// Wait for the worker to catch up, stalling the app.
+    #[default]
+    Block,
+
+    /// Drop all the data of the snapshot.
+    ///
+    /// Clears are never dropped: they get sent along with the next snapshot instead. The
+    /// components whose data was dropped get logged again as part of the next snapshot, which
+    /// might get dropped too if the worker still cannot keep up.
+    DropSnapshots,
+
+    /// Drop all the data produced by low priority loggers (see
+    /// [`crate::RerunLogger::with_low_priority`]), and send the rest along with the next snapshot.
+    ///
+    /// Clears are never dropped. The low priority components get logged again as part of the next
+    /// snapshot, just like with [`Self::DropSnapshots`]. Whatever isn't low priority keeps on
+    /// piling up for as long as the worker cannot keep up.
+    DropLowPriority,
+}
+
+// ---
+
+/// Some data that will be turned into a [`rerun::SerializedComponentBatch`] by the background
+/// logging worker, if it isn't one already.
+pub(crate) enum LogData {
+    Serialized(rerun::SerializedComponentBatch),
+
+    /// A snapshot of a component that must be logged using the generic reflection-based logger.
+    Reflected {
+        descriptor: rerun::ComponentDescriptor,
+        value: Option<Box<dyn PartialReflect>>,
+    },
+}
+
+/// A single batch waiting to be logged.
+pub(crate) struct QueuedBatch {
+    pub data: LogData,
+    pub low_priority: bool,
+
+    /// The component that produced this, if any, so that it can be logged again if this ever gets
+    /// dropped, see [`RerunQueueFullPolicy`].
+    pub component: Option<(Entity, ComponentId)>,
+}
+
+impl From<rerun::SerializedComponentBatch> for QueuedBatch {
+    #[inline]
+    fn from(batch: rerun::SerializedComponentBatch) -> Self {
+        Self {
+            data: LogData::Serialized(batch),
+            low_priority: false,
+            component: None,
+        }
+    }
+}
+
+impl QueuedBatch {
+    /// Whether this clears data rather than logging any.
+    pub fn is_clear(&self) -> bool {
+        match &self.data {
+            // NOTE: Empty batches are how individual descriptors get cleared.
+            LogData::Serialized(batch) => {
+                batch.descriptor == rerun::Clear::descriptor_is_recursive()
+                    || batch.array.is_empty()
+            }
+            LogData::Reflected { .. } => false,
+        }
+    }
+
+    /// How many bytes worth of component data this is.
+    ///
+    /// Reflected data doesn't count, we have no idea how big it'll be once serialized.
+    pub fn num_bytes(&self) -> u64 {
+        match &self.data {
+            LogData::Serialized(batch) => batch.array.get_array_memory_size() as u64,
+            LogData::Reflected { .. } => 0,
+        }
+    }
+
+    fn serialize(self, type_registry: &TypeRegistry) -> Option<rerun::SerializedComponentBatch> {
+        match self.data {
+            LogData::Serialized(batch) => Some(batch),
+            LogData::Reflected { descriptor, value } => {
+                let ron = value.and_then(|value| reflected_to_ron(value.as_ref(), type_registry));
+                reflected_component_batch(descriptor, ron)
+            }
+        }
+    }
+}
+
+/// Some data waiting to be logged at a given entity path.
+pub(crate) struct PendingLog {
+    pub time: CaptureTime,
+    pub entity_path: rerun::EntityPath,
+    pub batches: Vec<QueuedBatch>,
+}
+
+/// Everything that was flushed out of the [`crate::budget::LogQueue`] during a single snapshot.
+pub(crate) struct Snapshot {
+    pub batching: RerunSyncBatching,
+    pub logs: Vec<PendingLog>,
+}
+
+impl Snapshot {
+    /// Drops every batch that `keep` doesn't want to keep.
+    ///
+    /// Returns the components whose data got dropped.
+    fn retain(&mut self, mut keep: impl FnMut(&QueuedBatch) -> bool) -> Vec<(Entity, ComponentId)> {
+        let mut dropped = Vec::new();
+        for log in &mut self.logs {
+            log.batches.retain(|batch| {
+                let kept = keep(batch);
+                if !kept {
+                    dropped.extend(batch.component);
+                }
+                kept
+            });
+        }
+        self.logs.retain(|log| !log.batches.is_empty());
+        dropped
+    }
+}
+
+// ---
+
+/// Handle to the background thread that serializes and sends the synced data.
+///
+/// Dropping the handle waits for the worker to send everything that is still pending.
+pub(crate) struct LogWorker {
+    sender: Option<SyncSender<Snapshot>>,
+    thread: Option<JoinHandle<()>>,
+
+    /// Whatever couldn't be handed over yet because the worker couldn't keep up, see
+    /// [`RerunQueueFullPolicy`]. It goes first with the next snapshot.
+    held_back: Option<Snapshot>,
+}
+
+impl Drop for LogWorker {
+    fn drop(&mut self) {
+        if let (Some(sender), Some(held_back)) = (&self.sender, self.held_back.take()) {
+            _ = sender.send(held_back);
+        }
+
+        // Hanging up is what tells the worker to wrap up.
+        self.sender = None;
+
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("revy's logging worker panicked");
+            }
+        }
+    }
+}
+
+impl LogWorker {
+    /// `capacity` is the number of snapshots that can be waiting on the worker at once.
+    pub fn spawn(
+        rec: rerun::RecordingStream,
+        type_registry: AppTypeRegistry,
+        capacity: usize,
+    ) -> Self {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(capacity);
+
+        let thread = std::thread::Builder::new()
+            .name("revy_log_worker".to_owned())
+            .spawn(move || run_worker(&receiver, LogSink::new(rec), &type_registry));
+
+        match thread {
+            Ok(thread) => Self {
+                sender: Some(sender),
+                thread: Some(thread),
+                held_back: None,
+            },
+            Err(err) => {
+                error!(%err, "couldn't spawn revy's logging worker, nothing will be logged");
+                Self {
+                    sender: None,
+                    thread: None,
+                    held_back: None,
+                }
+            }
+        }
+    }
+
+    /// Hands `snapshot` over to the worker, applying `policy` if it cannot keep up.
+    ///
+    /// Returns the components whose data had to be dropped, which must be logged again.
+    #[must_use]
+    pub fn send(
+        &mut self,
+        mut snapshot: Snapshot,
+        policy: RerunQueueFullPolicy,
+    ) -> Vec<(Entity, ComponentId)> {
+        let Some(sender) = self.sender.as_ref() else {
+            return Vec::new();
+        };
+
+        if let Some(mut held_back) = self.held_back.take() {
+            held_back.logs.append(&mut snapshot.logs);
+            snapshot.logs = held_back.logs;
+        }
+
+        let mut snapshot = match sender.try_send(snapshot) {
+            Ok(()) | Err(TrySendError::Disconnected(_)) => return Vec::new(),
+            Err(TrySendError::Full(snapshot)) => snapshot,
+        };
+
+        let dropped = match policy {
+            RerunQueueFullPolicy::Block => {
+                _ = sender.send(snapshot);
+                return Vec::new();
+            }
+
+            RerunQueueFullPolicy::DropSnapshots => {
+                warn_once!("revy's logging worker cannot keep up, dropping snapshots");
+                snapshot.retain(QueuedBatch::is_clear)
+            }
+
+            RerunQueueFullPolicy::DropLowPriority => {
+                warn_once!("revy's logging worker cannot keep up, dropping low priority data");
+                snapshot.retain(|batch| batch.is_clear() || !batch.low_priority)
+            }
+        };
+
+        if !snapshot.logs.is_empty() {
+            self.held_back = Some(snapshot);
+        }
+
+        dropped
+    }
+}
+
+fn run_worker(receiver: &Receiver<Snapshot>, mut sink: LogSink, type_registry: &AppTypeRegistry) {
+    while let Ok(Snapshot { batching, logs }) = receiver.recv() {
+        let _trace = info_span!("log_snapshot").entered();
+
+        sink.set_batching(batching);
+
+        {
+            let type_registry = type_registry.read();
+            for PendingLog {
+                time,
+                entity_path,
+                batches,
+            } in logs
+            {
+                let batches = batches
+                    .into_iter()
+                    .filter_map(|batch| batch.serialize(&type_registry))
+                    .collect();
+                sink.log(time, entity_path, batches);
+            }
+        }
+
+        sink.end_snapshot();
+    }
+}